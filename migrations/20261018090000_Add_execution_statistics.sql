@@ -0,0 +1,12 @@
+/*  sqlite only supports adding a single column per statement, which is
+    why every column gets its own ALTER TABLE.
+*/
+ALTER TABLE executions ADD COLUMN position_x int;
+ALTER TABLE executions ADD COLUMN position_y int;
+ALTER TABLE executions ADD COLUMN steps_requested bigint;
+ALTER TABLE executions ADD COLUMN steps_moved bigint;
+ALTER TABLE executions ADD COLUMN steps_clamped bigint;
+ALTER TABLE executions ADD COLUMN min_x int;
+ALTER TABLE executions ADD COLUMN min_y int;
+ALTER TABLE executions ADD COLUMN max_x int;
+ALTER TABLE executions ADD COLUMN max_y int;
@@ -0,0 +1,4 @@
+/*  sqlite doesn't know about JSONB, it stores JSON as TEXT instead
+
+ALTER TABLE executions ADD COLUMN heatmap TEXT; */
+ALTER TABLE executions ADD COLUMN heatmap JSONB;
@@ -0,0 +1,4 @@
+ALTER TABLE executions ADD COLUMN parse_nanos bigint;
+ALTER TABLE executions ADD COLUMN simulation_nanos bigint;
+ALTER TABLE executions ADD COLUMN persistence_nanos bigint;
+ALTER TABLE executions ADD COLUMN serialization_nanos bigint;
@@ -0,0 +1,7 @@
+/*  The history of executions is filtered by and sorted by these columns. The `id` breaks
+    ties between executions with the same value, which is what cursors rely on.
+*/
+CREATE INDEX executions_timestamp_id ON executions (timestamp, id);
+CREATE INDEX executions_result_id ON executions (result, id);
+CREATE INDEX executions_commands_id ON executions (commands, id);
+CREATE INDEX executions_duration_id ON executions (duration, id);
@@ -0,0 +1,6 @@
+/*  sqlite doesn't know about JSONB, it stores JSON as TEXT instead
+
+ALTER TABLE executions ADD COLUMN request TEXT; */
+ALTER TABLE executions ADD COLUMN request JSONB;
+ALTER TABLE executions ADD COLUMN request_hash TEXT;
+CREATE INDEX executions_request_hash ON executions (request_hash);
@@ -0,0 +1 @@
+ALTER TABLE executions ADD COLUMN replay_of int REFERENCES executions (id);
@@ -0,0 +1,21 @@
+CREATE TABLE jobs (
+    /*  sqlite: id INTEGER PRIMARY KEY, */
+    id SERIAL PRIMARY KEY,
+
+    /*  sqlite: created TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+                updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL, */
+    created TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    status TEXT DEFAULT 'queued' NOT NULL,
+    progress float DEFAULT 0 NOT NULL,
+
+    /*  sqlite doesn't know about JSONB, it stores JSON as TEXT instead
+
+    request TEXT NOT NULL,
+    options TEXT NOT NULL, */
+    request JSONB NOT NULL,
+    options JSONB NOT NULL,
+    execution_id int REFERENCES executions (id),
+    error TEXT
+);
+CREATE INDEX jobs_status ON jobs (status);
@@ -0,0 +1,3 @@
+/*  A job parses its request when it's created, long before its execution is saved.
+*/
+ALTER TABLE jobs ADD COLUMN parse_nanos bigint;
@@ -0,0 +1,10 @@
+/*  sqlite doesn't know about comments on columns, the note below is all there is
+
+    The request is stored the way the client has sent it, i.e. with its patterns (see
+    `patterns.rs`), so replays expand them anew. Its hash is taken from the plain commands
+    the patterns were expanded into, so it's the same however the request was sent.
+*/
+COMMENT ON COLUMN executions.request IS
+    'The request as sent by the client, with its patterns as they were';
+COMMENT ON COLUMN executions.request_hash IS
+    'SHA-256 of the plain commands of the request, after expanding its patterns, in hex';
@@ -1,16 +1,50 @@
 use axum::{
-    extract::{Json, State},
-    response::Json as ResponseJson,
+    extract::{State, WebSocketUpgrade},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json as ResponseJson,
+    },
 };
-use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json as SqlJson, Pool, Postgres};
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::mpsc,
+    task::{self, JoinSet},
+};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
 
 use crate::{
-    execution::Execution,
-    types::{Command, Position},
+    clock::{Clock, Timestamp},
+    config::Config,
+    error::{correlation_id, Error, Path, Query},
+    execution::{CommandDetail, Execution, Simulation},
+    format::{columns, prefixed, Columns, Format, Negotiated, Payload},
+    heatmap::Heatmap,
+    history::{Cursor, Filter},
+    jobs::{Job, Status, Workers},
+    patterns::Plan,
+    render::{self, geojson::Origin},
+    session,
+    timing::{nanos, Timed, Timings},
+    trace::Trace,
+    types::{Command, Position, Region},
+    validation::{self, Issue, Report, Valid, Validate},
 };
 
+// The number of events waiting to be sent to a client before the simulation waits for it
+pub const STREAM_BUFFER: usize = 64;
+
 /*  A Request is a representation of the JSON spec delivered with the challenge.
     If the request has any other structure the server will respond unkind.
     The deserializes assures us that whatever data is being submitted to the API
@@ -27,27 +61,138 @@ use crate::{
     which allows for automatically generating certain classes and methods for operations
     relevant to the app (e.g. comparing on instance of a struct to another, for serializing
     and deserializing them into different formats etc.).
+
+    Requests may also contain cleaning `Pattern`s in between the commands. These are
+    expanded into plain commands while deserializing (that's what the `serde(from)`
+    attribute is for), so a `Request` itself only ever carries `Command`s.
 */
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(from = "Plan")]
 pub struct Request {
     pub start: Position,
     pub commands: Vec<Command>,
 }
 
+impl Request {
+    /*  The SHA-256 hash of the `Request` (in hex), which is the same for the same `start`
+        and `commands`, no matter how they were sent (e.g. as YAML, or using patterns). It
+        makes it easy to find executions of the same request.
+
+        It's taken from the fields themselves rather than from any serialization of them:
+        the coordinates and the steps as big-endian bytes and each direction as its place
+        in `Direction`. That can't fail, and a field added to the `Request` later on doesn't
+        change any hashes unless it's added here as well.
+    */
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.start.x.to_be_bytes());
+        hasher.update(self.start.y.to_be_bytes());
+        for command in &self.commands {
+            hasher.update([command.direction.clone() as u8]);
+            hasher.update(command.steps.to_be_bytes());
+        }
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
 /*  I've chosen to use the Option type here, which can either be a value ("Some")
     or "None" (e.g. empty) because the initial instance of a Response shouldn't
     contain any data it cannot know about itself just by existing. The relevant
     fields are then "filled" by combining its "default" data with the data of another
     class. In this case I'm creating any Response instance from an instance of Execution
     (the function for this is `impl` you see beneath this initializer).
+
+    `position` is where the robot ended up and `bounds` the bounding box of the area it has
+    cleaned. `steps_requested` is the sum of all the steps it was told to take, of which it
+    has taken `steps_moved` and dropped `steps_clamped` at the edge of the grid.
+
+    `timings` are the phases handling the request went through (see `Timings`), which
+    executions stored before they were measured don't have.
+
+    `request` is the `Plan` the `Execution` was calculated from (as it was sent, patterns and
+    all) and `request_hash` the hash of its commands, which are only shown when reading
+    executions back (there's no point in echoing the request to whoever has just sent it).
+    `replay_of` links a replay to its original.
+
+    `details`, `heatmap` and `trace` are only part of the output if they were asked for (see
+    `Options`), which is why they are skipped entirely instead of being serialized as `null`.
 */
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Response {
     id: Option<i32>,
-    timestamp: Option<DateTime<Local>>,
+    timestamp: Option<Timestamp>,
     commands: i32,
     result: i32,
     duration: Option<String>,
+    position: Option<Position>,
+    steps_requested: Option<i64>,
+    steps_moved: Option<i64>,
+    steps_clamped: Option<i64>,
+    bounds: Option<Region>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<Timings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request: Option<Plan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replay_of: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Vec<CommandDetail>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heatmap: Option<Heatmap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<Trace>,
+}
+
+/*  `Options` are the query parameters a client can pass along to `/path` to change
+    what the `Response` looks like, e.g. `/path?detail=commands`.
+
+    `heatmap=true` makes the `Response` include a `Heatmap` of the robot's visits, which
+    is stored alongside the `Execution` so it can be fetched again later on. The same goes
+    for `trace=true` and the `Trace` of the `Segment`s the robot has driven.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Options {
+    detail: Option<Detail>,
+    #[serde(default)]
+    heatmap: bool,
+    #[serde(default)]
+    trace: bool,
+}
+
+impl Options {
+    // Keeps whatever the client asked for from the `Simulation` with the `Execution`.
+    pub fn record(&self, execution: &mut Execution, simulation: &Simulation) {
+        if self.heatmap {
+            execution.heatmap = Some(SqlJson(Heatmap::from(&simulation.visits)));
+        }
+        if self.trace {
+            execution.trace = Some(simulation.trace.to_string());
+        }
+    }
+
+    // Adds the details the client asked for to the `Response`.
+    fn detail(&self, response: &mut Response, simulation: Simulation) {
+        if self.detail == Some(Detail::Commands) {
+            response.details = Some(simulation.details);
+        }
+    }
+}
+
+/*  The additional details a client can ask for:
+
+    - `commands`: a breakdown of every command (see `CommandDetail`)
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    Commands,
 }
 
 /*  `From` is a `Trait` in Rust, basically an interface for other classes you can choose
@@ -64,8 +209,17 @@ pub struct Response {
 */
 impl From<Execution> for Response {
     fn from(execution: Execution) -> Self {
-        // We want the local timezone attached to the returned JSON result
-        let timestamp: Option<DateTime<Local>> = execution.timestamp.map(|dt| DateTime::from(dt));
+        Response::localized(execution, &Clock::default())
+    }
+}
+
+impl Response {
+    /*  Converts an `Execution` just like `From` does, but with the `timestamp` formatted by
+        the given `Clock` (instead of the default one, using the timezone of the server).
+    */
+    pub fn localized(execution: Execution, clock: &Clock) -> Self {
+        // We want the timezone of the client attached to the returned JSON result
+        let timestamp = execution.timestamp.map(|t| clock.format(t));
 
         // We want to properly format the millisecond duration in seconds
         let duration = execution.duration.map(|d| format!("{:.6}", d));
@@ -76,35 +230,864 @@ impl From<Execution> for Response {
             commands: execution.commands,
             result: execution.result,
             duration,
+            position: execution.position(),
+            steps_requested: execution.steps_requested,
+            steps_moved: execution.steps_moved,
+            steps_clamped: execution.steps_clamped,
+            bounds: execution.bounds(),
+            timings: execution.timings(),
+            request: execution.request.map(|request| request.0),
+            request_hash: execution.request_hash,
+            replay_of: execution.replay_of,
+            details: None,
+            heatmap: execution.heatmap.map(|heatmap| heatmap.0),
+            trace: execution
+                .trace
+                .and_then(|trace| parse_trace(execution.id, &trace)),
         }
     }
 }
 
+impl Response {
+    /*  Adds the time it took to save the `Execution` to the `timings`, which is only known to
+        the request which saved it (see `Timings`).
+    */
+    pub fn with_persistence(mut self, persistence: Duration) -> Self {
+        let mut timings = self.timings.unwrap_or_default();
+        timings.persistence = Some(nanos(persistence));
+        self.timings = Some(timings);
+        self
+    }
+
+    /*  Adds the time it takes to encode the `Response` in `format` to the `timings`, so it
+        has to be the last change to it. The `Response` can't contain the time it took to
+        encode itself, so it's encoded once just for measuring how long it takes. It's the
+        same amount of work as the real thing (a number more or less in the output doesn't
+        make a difference).
+    */
+    pub fn with_serialization(mut self, format: Format) -> Self {
+        let mut timings = self.timings.unwrap_or_default();
+        timings.serialization = Some(0);
+        self.timings = Some(timings);
+
+        let start_time = Instant::now();
+        let _ = format.encode(&self);
+        timings.serialization = Some(nanos(start_time.elapsed()));
+        self.timings = Some(timings);
+        self
+    }
+
+    // The `id` of the `Execution`, once it has been saved.
+    pub fn id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+/*  A stored trace which can't be parsed (e.g. written by hand or by a broken version of the
+    app) shouldn't keep anyone from reading the rest of the `Execution`, so it's left out of
+    the `Response`. It's logged as an error though, since it means the data is corrupt.
+*/
+fn parse_trace(id: Option<i32>, trace: &str) -> Option<Trace> {
+    match trace.parse() {
+        Ok(trace) => Some(trace),
+        Err(error) => {
+            tracing::error!(?id, "Unable to parse stored trace of execution: {}", error);
+            None
+        }
+    }
+}
+
+/*  The columns of a `Response` as CSV, which include every field it may carry, whether it's
+    there or not. Anything which isn't a single value (the commands of the `request`, the
+    `details`, the runs of the `heatmap` and the `trace`) is written into its cell as JSON.
+*/
+impl Columns for Response {
+    fn columns() -> Vec<String> {
+        columns(&[
+            "id",
+            "timestamp",
+            "commands",
+            "result",
+            "duration",
+            "position_x",
+            "position_y",
+            "steps_requested",
+            "steps_moved",
+            "steps_clamped",
+            "bounds_min_x",
+            "bounds_min_y",
+            "bounds_max_x",
+            "bounds_max_y",
+            "timings_parse",
+            "timings_simulation",
+            "timings_persistence",
+            "timings_serialization",
+            "request_start_x",
+            "request_start_y",
+            "request_commands",
+            "request_hash",
+            "replay_of",
+            "details",
+            "heatmap_max",
+            "heatmap_mean",
+            "heatmap_revisited",
+            "heatmap_runs",
+            "trace",
+        ])
+    }
+}
+
 /*  The main handler/controller for the API path `/path`.
     On top of the request object itself and also receives state information
     from the main router (in this case the database connection pool).
 
     Its sole job is to receive the request, trigger the calculation for `Execution`
     required for `Response` and then build the `Response` object from the resulting
-    `Execution`.
+    `Execution`. The per-command details aren't stored in the database, so they have to
+    be taken from the `Simulation` instead. The `Response` is sent in whichever `Format`
+    the client accepts.
 
     It has as little ambiguity as possible, it's essentially a conduit (just like
     controllers should be). The heavy lifting should be done by the model itself.
 */
 pub async fn handle_enter_path(
     State(pool): State<Pool<Postgres>>,
-    Json(request): Json<Request>,
-) -> ResponseJson<Response> {
-    let execution = Execution::default();
-    let execution = execution.calculate(request).await;
-    let execution = execution
-        .save(pool)
+    Query(options): Query<Options>,
+    clock: Clock,
+    format: Format,
+    Timed(Valid(plan), parse): Timed<Valid<Plan>>,
+) -> Result<Negotiated<Response>, Error> {
+    let (mut execution, simulation) = calculate(Execution::default(), plan).await?;
+    execution.parse_nanos = Some(nanos(parse));
+    options.record(&mut execution, &simulation);
+
+    let (execution, persistence) = save(pool.clone(), execution).await?;
+
+    let mut response = Response::localized(execution, &clock).with_persistence(persistence);
+    response.request = None;
+    response.request_hash = None;
+    options.detail(&mut response, simulation);
+    let response = response.with_serialization(format);
+    record_timings(pool, &response);
+
+    Ok(Negotiated(format, response))
+}
+
+/*  Calculates the `Plan` on a thread of its own (see `Execution::calculate`), where it
+    doesn't hold up any other requests, and the handler can still be answered with a
+    timeout while the robot moves (see `error::time_out`). The simulation is stopped once
+    nobody waits for it anymore.
+*/
+async fn calculate(
+    execution: Execution,
+    plan: impl Into<Plan> + Send + 'static,
+) -> Result<(Execution, Simulation), Error> {
+    let cancel = Cancel::default();
+    let cancelled = cancel.0.clone();
+
+    task::spawn_blocking(move || execution.calculate(plan, |_| !cancelled.load(Ordering::Relaxed)))
         .await
-        .expect("Unable to save execution to database");
+        .map_err(|e| Error::Internal(format!("Unable to calculate request: {}", e)))?
+        .ok_or_else(|| Error::Internal("Calculation of request was stopped".to_string()))
+}
+
+/*  Saves the `Execution` in a task of its own, which finishes even if the handler is given
+    up on in the meantime (e.g. by `error::time_out`), instead of dropping the query halfway.
+    It also tells how long saving took.
+*/
+async fn save(pool: Pool<Postgres>, execution: Execution) -> Result<(Execution, Duration), Error> {
+    tokio::spawn(async move {
+        let start_time = Instant::now();
+        let execution = execution.save(pool).await?;
+        Ok((execution, start_time.elapsed()))
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Unable to save execution: {}", e)))?
+}
+
+/*  Persistence and serialization are only over once the `Execution` has been saved, so
+    their `Timings` are recorded in an update of their own (see `Execution::record_timings`).
+    There's no need to keep the client waiting for it, which is why it runs in the background.
+*/
+pub fn record_timings(pool: Pool<Postgres>, response: &Response) {
+    if let (Some(id), Some(timings)) = (response.id, response.timings) {
+        tokio::spawn(async move {
+            if let Err(e) = Execution::record_timings(&pool, id, timings).await {
+                tracing::error!("Unable to record timings of execution {}: {}", id, e);
+            }
+        });
+    }
+}
+
+/*  The same as `/path`, but the robot's progress is streamed to the client as Server-Sent
+    Events while it moves: a `progress` event after every command (see `Progress`) and a
+    final `result` event with the `Response` (or an `error` event with the `Error`). The
+    simulation runs on a thread of its own and stops as soon as the client goes away, in
+    which case nothing is saved.
+*/
+pub async fn handle_stream(
+    State(pool): State<Pool<Postgres>>,
+    Query(options): Query<Options>,
+    clock: Clock,
+    Timed(Valid(plan), parse): Timed<Valid<Plan>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    let correlation_id = correlation_id();
+
+    tokio::spawn(async move {
+        let outcome = stream_execution(pool, options, clock, plan, parse, sender.clone());
+        let event = match outcome.await {
+            Ok(Some(response)) => event("result", &response),
+            Ok(None) => return,
+            Err(error) => {
+                tracing::error!(%correlation_id, kind = error.kind(), "{:?}", error);
+                event("error", &error.body(correlation_id))
+            }
+        };
+        let _ = sender.send(Ok(event)).await;
+    });
+
+    Sse::new(ReceiverStream::new(receiver)).keep_alive(KeepAlive::default())
+}
+
+/*  Calculates and saves the `Execution` for `handle_stream`, sending a `progress` event
+    from within the loop of the simulation. There's no `Response` if the client has gone
+    away in the meantime.
+*/
+async fn stream_execution(
+    pool: Pool<Postgres>,
+    options: Options,
+    clock: Clock,
+    plan: Plan,
+    parse: Duration,
+    sender: mpsc::Sender<Result<Event, Infallible>>,
+) -> Result<Option<Response>, Error> {
+    let outcome = task::spawn_blocking(move || {
+        Execution::default().calculate(plan, |progress| {
+            sender
+                .blocking_send(Ok(event("progress", progress)))
+                .is_ok()
+        })
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Unable to calculate request: {}", e)))?;
+
+    let (mut execution, simulation) = match outcome {
+        Some(outcome) => outcome,
+        None => return Ok(None),
+    };
+    execution.parse_nanos = Some(nanos(parse));
+    options.record(&mut execution, &simulation);
+
+    let (execution, persistence) = save(pool.clone(), execution).await?;
+    let mut response = Response::localized(execution, &clock).with_persistence(persistence);
+    response.request = None;
+    response.request_hash = None;
+    options.detail(&mut response, simulation);
+    let response = response.with_serialization(Format::Json);
+    record_timings(pool, &response);
+
+    Ok(Some(response))
+}
+
+// An event of the stream with `value` as its JSON data.
+fn event(kind: &str, value: &impl Serialize) -> Event {
+    match Event::default().event(kind).json_data(value) {
+        Ok(event) => event,
+        Err(e) => Event::default().event("error").data(e.to_string()),
+    }
+}
+
+// Opens an interactive `Session` over a WebSocket, see `session.rs`.
+pub async fn handle_session(
+    State(pool): State<Pool<Postgres>>,
+    clock: Clock,
+    socket: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let correlation_id = correlation_id();
+    socket.on_upgrade(move |socket| session::run(socket, pool, clock, correlation_id))
+}
+
+/*  An item of a batch is either the `Response` for its request or the `Error` (as JSON)
+    which kept it from being calculated.
+*/
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum BatchItem {
+    Execution(Box<Response>),
+    Error(Value),
+}
+
+// A `BatchItem` has the columns of a `Response` followed by the ones of an `Error`.
+impl Columns for BatchItem {
+    fn columns() -> Vec<String> {
+        [
+            Response::columns(),
+            columns(&["error", "kind", "correlation_id", "violations", "line"]),
+        ]
+        .concat()
+    }
+}
+
+/*  Takes a whole list of requests at once (up to the `max_batch_size` of the `Config`), for
+    jobs submitting thousands of plans. The requests are calculated concurrently, each one
+    on a thread of its own, and once all of them are done the results are saved in a single
+    transaction: either all of them end up in the database or none of them do. If any of
+    them can't be calculated the others are stopped right away.
+
+    A request which isn't valid doesn't spoil the whole batch, it's reported in its own
+    item instead (with the paths of its violations starting at its index, e.g.
+    `$[3].commands[0].steps`). The items are in the same order as the requests. The same
+    query parameters as for `/path` apply to every single one of them.
+*/
+pub async fn handle_batch(
+    State(pool): State<Pool<Postgres>>,
+    State(config): State<Config>,
+    Query(options): Query<Options>,
+    clock: Clock,
+    format: Format,
+    Timed(Payload(requests), parse): Timed<Payload<Vec<Plan>>>,
+) -> Result<Negotiated<Vec<BatchItem>>, Error> {
+    if requests.len() > config.max_batch_size {
+        return Err(Error::Validation(vec![Issue {
+            path: "$".to_string(),
+            message: format!(
+                "{} requests exceed the batch limit of {}",
+                requests.len(),
+                config.max_batch_size
+            ),
+        }]));
+    }
+
+    let parse = parse / requests.len().max(1) as u32;
+    let cancel = Cancel::default();
+    let mut tasks = JoinSet::new();
+    let mut outcomes: Vec<Option<(Execution, Simulation)>> = vec![];
+    let mut items: Vec<Option<BatchItem>> = vec![];
+
+    for (index, plan) in requests.into_iter().enumerate() {
+        let violations = violations_at(index, &plan);
+        outcomes.push(None);
+        if violations.is_empty() {
+            let cancelled = cancel.0.clone();
+            tasks.spawn(async move {
+                let outcome = task::spawn_blocking(move || {
+                    Execution::default().calculate(plan, |_| !cancelled.load(Ordering::Relaxed))
+                })
+                .await;
+                (index, outcome)
+            });
+            items.push(None);
+        } else {
+            let error = Error::Validation(violations);
+            items.push(Some(BatchItem::Error(error.body(correlation_id()))));
+        }
+    }
+
+    /*  The transaction is only opened once every request has been calculated, so it isn't
+        kept open (and a connection taken from the pool) while the robots move.
+    */
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined
+            .and_then(|(index, outcome)| outcome.map(|outcome| (index, outcome)))
+            .map_err(|e| Error::Internal(format!("Unable to calculate request: {}", e)))?;
+        outcomes[index] = outcome;
+    }
+
+    // Just like `save`, the transaction is never dropped halfway, it's committed or rolled back.
+    let items = tokio::spawn(async move {
+        let mut transaction = pool.begin().await?;
+        for (item, outcome) in items.iter_mut().zip(outcomes) {
+            if let Some((mut execution, simulation)) = outcome {
+                execution.parse_nanos = Some(nanos(parse));
+                options.record(&mut execution, &simulation);
+
+                let start_time = Instant::now();
+                let execution = execution.save_with(&mut transaction).await?;
+                let mut response =
+                    Response::localized(execution, &clock).with_persistence(start_time.elapsed());
+                response.request = None;
+                response.request_hash = None;
+                options.detail(&mut response, simulation);
+                let response = response.with_serialization(format);
+                *item = Some(BatchItem::Execution(Box::new(response)));
+            }
+        }
+        transaction.commit().await?;
+
+        for item in items.iter().flatten() {
+            if let BatchItem::Execution(response) = item {
+                record_timings(pool.clone(), response);
+            }
+        }
+
+        Ok::<_, Error>(items)
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Unable to save batch: {}", e)))??;
+
+    Ok(Negotiated(format, items.into_iter().flatten().collect()))
+}
+
+/*  Tells the simulations of a batch to stop once it's dropped, which happens whenever the
+    batch is done, including when it has failed early or the client has gone away (dropping
+    the `JoinSet` only aborts the tasks waiting for the simulations, not the simulations
+    themselves, which run on threads of their own).
+*/
+#[derive(Default)]
+struct Cancel(Arc<AtomicBool>);
+
+impl Drop for Cancel {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// The violations of the request at `index` of a batch, with paths starting at that index.
+fn violations_at(index: usize, plan: &Plan) -> Vec<Issue> {
+    plan.violations()
+        .into_iter()
+        .map(|issue| Issue {
+            path: issue.path.replacen('$', &format!("$[{}]", index), 1),
+            message: issue.message,
+        })
+        .collect()
+}
+
+/*  Checks a request without simulating it, let alone saving it (see `validation::validate`),
+    e.g. before sending a plan to a robot. The `Report` lists what's wrong with the request,
+    but the request itself is always answered with a `200`, whether it's valid or not.
+*/
+pub async fn handle_validate(format: Format, Payload(plan): Payload<Plan>) -> Negotiated<Report> {
+    Negotiated(format, validation::validate(&plan))
+}
+
+/*  Renders the path of a request as a PNG image, e.g. for attaching it to a ticket.
+    Since this is only about looking at a request the `Execution` isn't saved.
+*/
+pub async fn handle_render_png(Valid(request): Valid<Request>) -> Result<impl IntoResponse, Error> {
+    let start = request.start;
+    let (_, simulation) = calculate(Execution::default(), request).await?;
+
+    png(start, &simulation)
+}
+
+// Renders the path of a stored `Execution` as a PNG image (see `resimulate`).
+pub async fn handle_execution_png(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let (_, request, simulation) = resimulate(pool, id).await?;
+
+    png(request.start, &simulation)
+}
+
+fn png(start: Position, simulation: &Simulation) -> Result<impl IntoResponse, Error> {
+    let image = render::png::render(start, simulation)
+        .map_err(|e| Error::Internal(format!("Unable to render PNG: {}", e)))?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], image))
+}
+
+// Renders the path of a request as an SVG image, just like `handle_render_png`.
+pub async fn handle_render_svg(Valid(request): Valid<Request>) -> Result<impl IntoResponse, Error> {
+    let (_, simulation) = calculate(Execution::default(), request.clone()).await?;
+
+    Ok(svg(&request, &simulation))
+}
+
+// Renders the path of a stored `Execution` as an SVG image (see `resimulate`).
+pub async fn handle_execution_svg(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let (_, request, simulation) = resimulate(pool, id).await?;
+
+    Ok(svg(&request, &simulation))
+}
+
+fn svg(request: &Request, simulation: &Simulation) -> impl IntoResponse {
+    let image = render::svg::render(request.start, &request.commands, simulation);
+
+    ([(header::CONTENT_TYPE, "image/svg+xml")], image)
+}
+
+/*  Renders the path of a request as a plain text map, which is only possible for small
+    paths (see `render::ascii`). Bigger ones are refused with a `422`.
+*/
+pub async fn handle_render_ascii(
+    Valid(request): Valid<Request>,
+) -> Result<impl IntoResponse, Error> {
+    let start = request.start;
+    let (_, simulation) = calculate(Execution::default(), request).await?;
+
+    ascii(start, &simulation)
+}
 
-    let response = Response::from(execution);
+// Renders the path of a stored `Execution` as a plain text map (see `resimulate`).
+pub async fn handle_execution_ascii(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let (_, request, simulation) = resimulate(pool, id).await?;
+
+    ascii(request.start, &simulation)
+}
+
+fn ascii(start: Position, simulation: &Simulation) -> Result<impl IntoResponse, Error> {
+    match render::ascii::render(start, simulation) {
+        Some(map) => Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], map)),
+        None => Err(Error::Unprocessable(format!(
+            "Path exceeds {} columns or rows, which is too large for a text map",
+            render::ascii::MAX_SIZE
+        ))),
+    }
+}
+
+/*  Exports the path of a request as GeoJSON, converting the grid into metres as told
+    by the `Origin` in the query parameters (e.g. `?origin_x=10&origin_y=5&cell_size=0.3`).
+*/
+pub async fn handle_render_geojson(
+    Query(origin): Query<Origin>,
+    Valid(request): Valid<Request>,
+) -> Result<impl IntoResponse, Error> {
+    let start = request.start;
+    let (execution, simulation) = calculate(Execution::default(), request).await?;
+
+    Ok(geojson(start, &simulation, &execution, origin))
+}
+
+/*  Exports the path of a stored `Execution` as GeoJSON (see `resimulate`), with the `id`
+    and `timestamp` of the stored one among its metadata.
+*/
+pub async fn handle_execution_geojson(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+    Query(origin): Query<Origin>,
+) -> Result<impl IntoResponse, Error> {
+    let (execution, request, simulation) = resimulate(pool, id).await?;
+
+    Ok(geojson(request.start, &simulation, &execution, origin))
+}
+
+fn geojson(
+    start: Position,
+    simulation: &Simulation,
+    execution: &Execution,
+    origin: Origin,
+) -> impl IntoResponse {
+    let geojson = render::geojson::render(start, simulation, execution, origin);
+
+    (
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        geojson.to_string(),
+    )
+}
+
+/*  Returns a stored `Execution` in the same shape as `/path` does (apart from the per-command
+    details, which aren't stored), or a `404` if there isn't one with the given `id`.
+*/
+pub async fn handle_execution(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+    clock: Clock,
+    format: Format,
+) -> Result<Negotiated<Response>, Error> {
+    match Execution::find(pool, id).await? {
+        Some(execution) => Ok(Negotiated(format, Response::localized(execution, &clock))),
+        None => Err(not_found(id)),
+    }
+}
+
+/*  A `Page` of the history of executions, along with the cursor pointing to the next one
+    (which is `None` for the last page).
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Page {
+    executions: Vec<Response>,
+    next_cursor: Option<String>,
+}
+
+impl Columns for Page {
+    fn columns() -> Vec<String> {
+        columns(&["executions", "next_cursor"])
+    }
+}
+
+/*  Browses the stored executions a `Page` at a time, filtered and sorted as told by the
+    query parameters (see `Filter`), e.g. `/executions?min_result=100&sort=duration`. The
+    next page is fetched by passing its `next_cursor` along as `cursor`, with the same
+    filters. A broken cursor is answered with a `400`.
+*/
+pub async fn handle_executions(
+    State(pool): State<Pool<Postgres>>,
+    Query(filter): Query<Filter>,
+    clock: Clock,
+    format: Format,
+) -> Result<Negotiated<Page>, Error> {
+    let mut executions = Execution::list(pool, &filter).await?;
+
+    let limit = filter.limit() as usize;
+    let next_cursor = if executions.len() > limit {
+        executions.truncate(limit);
+        executions
+            .last()
+            .and_then(|execution| Cursor::after(execution, filter.sort))
+            .map(|cursor| cursor.to_string())
+    } else {
+        None
+    };
+
+    let executions = executions
+        .into_iter()
+        .map(|execution| Response::localized(execution, &clock))
+        .collect();
+
+    Ok(Negotiated(
+        format,
+        Page {
+            executions,
+            next_cursor,
+        },
+    ))
+}
+
+/*  The outcome of replaying an `Execution`: the new one (linked to the original by its
+    `replay_of`), the `result` of the original and whether the replay came to a different one.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    execution: Response,
+    original_result: i32,
+    result_changed: bool,
+}
+
+impl Columns for Replay {
+    fn columns() -> Vec<String> {
+        [
+            prefixed("execution", Response::columns()),
+            columns(&["original_result", "result_changed"]),
+        ]
+        .concat()
+    }
+}
+
+/*  Runs the stored request of an `Execution` again, e.g. after the engine, the rules at the
+    edge of the grid or the patterns have changed (its `Plan` is stored the way it was sent,
+    so the patterns are expanded anew), and saves the outcome as a new `Execution`. There's
+    nothing to replay for executions which don't exist (`404`) or were stored before their
+    requests were (`422`).
+*/
+pub async fn handle_replay(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+    clock: Clock,
+    format: Format,
+) -> Result<Negotiated<Replay>, Error> {
+    let original = Execution::find(pool.clone(), id)
+        .await?
+        .ok_or_else(|| not_found(id))?;
+
+    let plan = original
+        .request
+        .ok_or_else(|| Error::Unprocessable(format!("Execution {} has no request recorded", id)))?;
+
+    let execution = Execution {
+        replay_of: Some(id),
+        ..Execution::default()
+    };
+    let (execution, _) = calculate(execution, plan.0).await?;
+    let (execution, persistence) = save(pool.clone(), execution).await?;
+    let result_changed = execution.result != original.result;
+    let response = Response::localized(execution, &clock)
+        .with_persistence(persistence)
+        .with_serialization(format);
+    record_timings(pool, &response);
+
+    Ok(Negotiated(
+        format,
+        Replay {
+            original_result: original.result,
+            result_changed,
+            execution: response,
+        },
+    ))
+}
+
+/*  Only the outcome of an `Execution` is stored, not the path the robot took, so rendering
+    a stored one means running its `Request` again (without saving anything). The outcome is
+    the same as the first time, unless the engine has changed in between (see `handle_replay`).
+    Executions which don't exist are answered with a `404`, the ones stored before their
+    requests were with a `422`.
+*/
+async fn resimulate(
+    pool: Pool<Postgres>,
+    id: i32,
+) -> Result<(Execution, Request, Simulation), Error> {
+    let mut execution = Execution::find(pool, id)
+        .await?
+        .ok_or_else(|| not_found(id))?;
+
+    let plan = execution
+        .request
+        .take()
+        .ok_or_else(|| Error::Unprocessable(format!("Execution {} has no request recorded", id)))?
+        .0;
+    let request = Request::from(plan);
+
+    let (_, simulation) = calculate(Execution::default(), request.clone()).await?;
+
+    Ok((execution, request, simulation))
+}
+
+/*  Returns the `Heatmap` of a stored `Execution`. There are two reasons for there not
+    being one: the `Execution` doesn't exist or the client didn't ask for a `Heatmap` when
+    it was created. Both are answered with a `404`, but with a different message.
+*/
+pub async fn handle_heatmap(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+) -> Result<ResponseJson<Heatmap>, Error> {
+    match Execution::find(pool, id).await? {
+        Some(Execution {
+            heatmap: Some(heatmap),
+            ..
+        }) => Ok(ResponseJson(heatmap.0)),
+        Some(_) => Err(Error::NotFound(format!(
+            "Execution {} has no heatmap recorded",
+            id
+        ))),
+        None => Err(not_found(id)),
+    }
+}
+
+/*  What a client gets to see of a `Job`: its `status`, its `progress` (in percent) and,
+    once it has completed, the `result`, which is the `Response` of the `Execution` it has
+    saved. A failed job tells what went wrong in its `error`.
+*/
+#[derive(Serialize, Debug, Clone)]
+pub struct JobResponse {
+    pub id: i32,
+    pub status: Status,
+    pub progress: f64,
+    pub created: Timestamp,
+    pub updated: Timestamp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Response>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Columns for JobResponse {
+    fn columns() -> Vec<String> {
+        [
+            columns(&["id", "status", "progress", "created", "updated"]),
+            prefixed("result", Response::columns()),
+            columns(&["error"]),
+        ]
+        .concat()
+    }
+}
+
+impl JobResponse {
+    async fn load(pool: Pool<Postgres>, job: Job, clock: &Clock) -> Result<Self, Error> {
+        let result = match job.execution_id {
+            Some(id) => Execution::find(pool, id).await?.map(|execution| {
+                let mut response = Response::localized(execution, clock);
+                response.request = None;
+                response.request_hash = None;
+                response
+            }),
+            None => None,
+        };
+
+        Ok(JobResponse {
+            id: job.id,
+            status: job.status,
+            progress: job.progress,
+            created: clock.format(job.created),
+            updated: clock.format(job.updated),
+            result,
+            error: job.error,
+        })
+    }
+}
+
+/*  Takes the same request (and query parameters) as `/path`, but only stores it as a `Job`
+    and answers right away with a `202 Accepted`, pointing to the job in its `Location`
+    header. The robot moves once one of the `Workers` gets to it, see `jobs.rs`.
+
+    The `details` of the commands aren't available for jobs, since they aren't stored.
+*/
+pub async fn handle_create_job(
+    State(pool): State<Pool<Postgres>>,
+    State(workers): State<Workers>,
+    Query(options): Query<Options>,
+    clock: Clock,
+    format: Format,
+    Timed(Valid(plan), parse): Timed<Valid<Plan>>,
+) -> Result<impl IntoResponse, Error> {
+    let job = Job::create(pool.clone(), plan, options, parse).await?;
+    workers.wake();
+
+    let location = format!("/jobs/{}", job.id);
+    let job = JobResponse::load(pool, job, &clock).await?;
 
-    ResponseJson(response)
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, location)],
+        Negotiated(format, job),
+    ))
+}
+
+pub async fn handle_job(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+    clock: Clock,
+    format: Format,
+) -> Result<Negotiated<JobResponse>, Error> {
+    let job = Job::find(pool.clone(), id)
+        .await?
+        .ok_or_else(|| job_not_found(id))?;
+
+    Ok(Negotiated(
+        format,
+        JobResponse::load(pool, job, &clock).await?,
+    ))
+}
+
+/*  Cancels a `Job` which hasn't finished yet. Cancelling a job twice is fine, but one which
+    has already completed or failed can't be cancelled anymore (`422`).
+*/
+pub async fn handle_cancel_job(
+    State(pool): State<Pool<Postgres>>,
+    Path(id): Path<i32>,
+    clock: Clock,
+    format: Format,
+) -> Result<Negotiated<JobResponse>, Error> {
+    let job = Job::cancel(pool.clone(), id)
+        .await?
+        .ok_or_else(|| job_not_found(id))?;
+
+    if job.status.is_final() && job.status != Status::Cancelled {
+        return Err(Error::Unprocessable(format!(
+            "Job {} has already finished",
+            id
+        )));
+    }
+
+    Ok(Negotiated(
+        format,
+        JobResponse::load(pool, job, &clock).await?,
+    ))
+}
+
+fn job_not_found(id: i32) -> Error {
+    Error::NotFound(format!("Job {} not found", id))
+}
+
+fn not_found(id: i32) -> Error {
+    Error::NotFound(format!("Execution {} not found", id))
 }
 
 #[cfg(test)]
@@ -113,7 +1096,7 @@ mod test {
     use std::fs;
 
     use super::*;
-    use crate::types::Direction;
+    use crate::{execution::Progress, types::Direction};
 
     /*  This test assures that we always carry a proper local timezone in our
         response output, despite working with UTC otherwise
@@ -132,19 +1115,313 @@ mod test {
             commands: 3,
             result: 10,
             duration: Some(0.000023),
+            position_x: Some(-4),
+            position_y: Some(2),
+            steps_requested: Some(12),
+            steps_moved: Some(11),
+            steps_clamped: Some(1),
+            min_x: Some(-4),
+            min_y: Some(0),
+            max_x: Some(3),
+            max_y: Some(2),
+            heatmap: None,
+            trace: Some("0,0 E3 N2+1 W7".to_string()),
+            parse_nanos: Some(1200),
+            simulation_nanos: Some(23000),
+            persistence_nanos: Some(4100),
+            serialization_nanos: None,
+            request: None,
+            request_hash: None,
+            replay_of: None,
         };
 
         let response = Response::from(execution);
 
         assert_eq!(
-            Some("2014-11-28T13:00:09.000000001+01:00".to_string()),
-            response.timestamp.map(|dt| format!("{:?}", dt))
+            Some(Timestamp::Text(
+                "2014-11-28T13:00:09.000000001+01:00".to_string()
+            )),
+            response.timestamp
         );
 
         assert_eq!(Some("0.000023".to_string()), response.duration);
         assert_eq!(3, response.commands);
         assert_eq!(10, response.result);
         assert_eq!(Some(1), response.id);
+        assert_eq!(Some(Position { x: -4, y: 2 }), response.position);
+        assert_eq!(
+            (Some(12), Some(11), Some(1)),
+            (
+                response.steps_requested,
+                response.steps_moved,
+                response.steps_clamped
+            )
+        );
+        assert_eq!(
+            Some(Region {
+                min: Position { x: -4, y: 0 },
+                max: Position { x: 3, y: 2 },
+            }),
+            response.bounds
+        );
+        assert_eq!(
+            Some(Timings {
+                parse: Some(1200),
+                simulation: Some(23000),
+                persistence: Some(4100),
+                serialization: None,
+            }),
+            response.timings
+        );
+        assert_eq!(
+            Some(vec![Direction::East, Direction::North, Direction::West]),
+            response
+                .trace
+                .map(|trace| trace.0.into_iter().map(|s| s.direction).collect())
+        );
+
+        let corrupt = Response::from(Execution {
+            trace: Some("0,0 E3 up".to_string()),
+            ..Execution::default()
+        });
+        assert!(corrupt.trace.is_none());
+    }
+
+    #[test]
+    fn lists_every_field_as_csv_column() {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![Command {
+                direction: Direction::East,
+                steps: 1,
+            }],
+        };
+        let execution = Execution {
+            id: Some(1),
+            duration: Some(0.1),
+            position_x: Some(1),
+            position_y: Some(0),
+            steps_requested: Some(1),
+            steps_moved: Some(1),
+            steps_clamped: Some(0),
+            min_x: Some(0),
+            min_y: Some(0),
+            max_x: Some(1),
+            max_y: Some(0),
+            heatmap: Some(SqlJson(Heatmap::default())),
+            trace: Some("0,0 E1".to_string()),
+            parse_nanos: Some(1),
+            simulation_nanos: Some(1),
+            request_hash: Some(request.hash()),
+            request: Some(SqlJson(Plan::from(request))),
+            replay_of: Some(1),
+            ..Execution::default()
+        };
+        let response = Response {
+            details: Some(vec![]),
+            ..Response::from(execution)
+                .with_persistence(Duration::from_micros(1))
+                .with_serialization(Format::Csv)
+        };
+
+        let mut row = serde_json::Map::new();
+        crate::format::flatten("", &serde_json::to_value(response).unwrap(), &mut row);
+
+        let columns = Response::columns();
+        let missing: Vec<&String> = row.keys().filter(|key| !columns.contains(key)).collect();
+        assert!(missing.is_empty(), "missing columns {:?}", missing);
+        assert_eq!(row.len(), columns.len());
+    }
+
+    #[test]
+    fn hashes_requests_by_content() {
+        let json: Request = serde_json::from_str(
+            r#"{"start": {"x": 0, "y": 0}, "commands": [{"direction": "east", "steps": 2}]}"#,
+        )
+        .unwrap();
+        let yaml: Request = serde_yaml::from_str(
+            "commands:\n  - {steps: 2, direction: east}\nstart: {y: 0, x: 0}\n",
+        )
+        .unwrap();
+        let other: Request = serde_json::from_str(
+            r#"{"start": {"x": 0, "y": 0}, "commands": [{"direction": "east", "steps": 3}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(json.hash(), yaml.hash());
+        assert_eq!(json.hash().len(), 64);
+        assert_ne!(json.hash(), other.hash());
+
+        let hashes: Vec<String> = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .into_iter()
+        .map(|direction| {
+            Request {
+                start: Position { x: -3, y: 100000 },
+                commands: vec![Command {
+                    direction,
+                    steps: -7,
+                }],
+            }
+            .hash()
+        })
+        .collect();
+        assert!(hashes[1..].iter().all(|hash| hash != &hashes[0]));
+
+        // Stored hashes have to stay the same, whatever becomes of `Request`
+        assert_eq!(
+            json.hash(),
+            "c7ca155fb453d6f18c0f934d55d16b2a78e9ab372f9ba8900f42b1d30166c39d"
+        );
+    }
+
+    #[test]
+    fn points_violations_into_batch() {
+        let plan: Plan = serde_json::from_str(
+            r#"{"start": {"x": 100001, "y": 0}, "commands": [{"direction": "east", "steps": -1}]}"#,
+        )
+        .unwrap();
+
+        let paths: Vec<String> = violations_at(3, &plan)
+            .into_iter()
+            .map(|issue| issue.path)
+            .collect();
+        assert_eq!(paths, vec!["$[3].start.x", "$[3].commands[0].steps"]);
+
+        let item = BatchItem::Error(Error::Validation(vec![]).body("request-1".to_string()));
+        assert_eq!(serde_json::to_value(item).unwrap()["kind"], "validation");
+    }
+
+    // Reads a whole stream of Server-Sent Events, as the name and the data of every event.
+    async fn events(response: axum::response::Response) -> Vec<(String, Value)> {
+        use axum::body::HttpBody;
+
+        let mut body = response.into_body();
+        let mut bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+
+        String::from_utf8(bytes)
+            .unwrap()
+            .split("\n\n")
+            .filter_map(|event| {
+                let name = event.lines().find_map(|line| line.strip_prefix("event:"))?;
+                let data = event.lines().find_map(|line| line.strip_prefix("data:"))?;
+                Some((name.to_string(), serde_json::from_str(data).unwrap()))
+            })
+            .collect()
+    }
+
+    // A `Response` without the fields which are different every time it's calculated.
+    fn comparable(mut response: Value) -> Value {
+        for field in ["id", "timestamp", "duration", "timings"] {
+            response.as_object_mut().unwrap().remove(field);
+        }
+        response
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn streams_progress_of_every_command(pool: Pool<Postgres>) {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 2,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 1,
+                },
+                Command {
+                    direction: Direction::West,
+                    steps: 1,
+                },
+            ],
+        };
+
+        let sse = handle_stream(
+            State(pool.clone()),
+            Query(Options::default()),
+            Clock::default(),
+            Timed(Valid(Plan::from(request.clone())), Duration::ZERO),
+        )
+        .await;
+        let mut events = events(sse.into_response()).await;
+        let (name, result) = events.pop().unwrap();
+        assert_eq!(name, "result");
+
+        let progress: Vec<Progress> = events
+            .into_iter()
+            .map(|(name, data)| {
+                assert_eq!(name, "progress");
+                serde_json::from_value(data).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            progress
+                .iter()
+                .map(|progress| (progress.command, progress.cleaned, progress.percent))
+                .collect::<Vec<_>>(),
+            vec![(1, 2, 50.0), (2, 3, 75.0), (3, 4, 100.0)]
+        );
+        assert_eq!(progress[2].position, Position { x: 1, y: 1 });
+
+        let Negotiated(_, response) = handle_enter_path(
+            State(pool),
+            Query(Options::default()),
+            Clock::default(),
+            Format::Json,
+            Timed(Valid(Plan::from(request)), Duration::ZERO),
+        )
+        .await
+        .unwrap();
+        assert_ne!(result["id"], serde_json::to_value(&response).unwrap()["id"]);
+        assert_eq!(
+            comparable(result),
+            comparable(serde_json::to_value(response).unwrap())
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn stops_streaming_without_a_client(pool: Pool<Postgres>) {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 2,
+                };
+                10
+            ],
+        };
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        drop(receiver);
+
+        let outcome = stream_execution(
+            pool.clone(),
+            Options::default(),
+            Clock::default(),
+            Plan::from(request),
+            Duration::ZERO,
+            sender,
+        )
+        .await
+        .unwrap();
+        assert!(outcome.is_none());
+
+        let (executions,): (i64,) = sqlx::query_as("select count(*) from executions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(executions, 0);
     }
 
     /*  These tests are mainly parsing fixtures, taken from the challenge document,
@@ -166,7 +1443,7 @@ mod test {
         );
 
         let execution = Execution::default();
-        let execution = execution.calculate(request).await;
+        let execution = execution.calculate(request, |_| true).unwrap_or_default().0;
         assert_eq!((execution.commands, execution.result), (2, 3));
 
         let file = fs::read_to_string("test/fixtures/example_request_negative.json")
@@ -182,7 +1459,7 @@ mod test {
         );
 
         let execution = Execution::default();
-        let execution = execution.calculate(request).await;
+        let execution = execution.calculate(request, |_| true).unwrap_or_default().0;
         assert_eq!((execution.commands, execution.result), (2, 2));
 
         let file = fs::read_to_string("test/fixtures/example_request_10_commands.json")
@@ -198,7 +1475,7 @@ mod test {
         );
 
         let execution = Execution::default();
-        let execution = execution.calculate(request).await;
+        let execution = execution.calculate(request, |_| true).unwrap_or_default().0;
         assert_eq!((execution.commands, execution.result), (10, 15688));
     }
 }
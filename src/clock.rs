@@ -0,0 +1,177 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Query};
+
+// The header a client can send its timezone in, instead of using the `tz` query parameter
+pub const TIMEZONE_HEADER: &str = "time-zone";
+
+/*  The ways a `timestamp` can be written in a `Response`:
+
+    - `rfc3339`: e.g. `2014-11-28T13:00:09.000001+01:00` (the default)
+    - `rfc2822`: e.g. `Fri, 28 Nov 2014 13:00:09 +0100`
+    - `epoch_millis`: the milliseconds since the Unix epoch, as a number
+*/
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    #[default]
+    Rfc3339,
+    Rfc2822,
+    EpochMillis,
+}
+
+/*  A formatted `timestamp`. The epoch milliseconds are a plain number, the other formats
+    are text, which is why it's serialized `untagged` (without telling which one it is).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Timestamp {
+    Millis(i64),
+    Text(String),
+}
+
+/*  The `Clock` decides how a client gets to see timestamps: in which timezone and in
+    which `TimestampFormat`. Without a `timezone` the timezone of the server is used
+    (which is set with `TZ`, see the Dockerfile).
+
+    Our teams sit in several timezones, so every request can ask for its own one, either
+    with the `tz` query parameter or the `Time-Zone` header (the query parameter wins),
+    taking an IANA name such as `Europe/Berlin`. The format is chosen with the
+    `timestamp_format` query parameter.
+*/
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Clock {
+    pub timezone: Option<Tz>,
+    pub format: TimestampFormat,
+}
+
+// The query parameters a `Clock` is built from.
+#[derive(Deserialize, Debug, Default)]
+struct ClockQuery {
+    tz: Option<String>,
+    #[serde(default)]
+    timestamp_format: TimestampFormat,
+}
+
+impl Clock {
+    pub fn format(&self, timestamp: DateTime<Utc>) -> Timestamp {
+        match (self.format, self.timezone) {
+            (TimestampFormat::EpochMillis, _) => Timestamp::Millis(timestamp.timestamp_millis()),
+            (TimestampFormat::Rfc3339, Some(tz)) => {
+                Timestamp::Text(timestamp.with_timezone(&tz).to_rfc3339())
+            }
+            (TimestampFormat::Rfc3339, None) => {
+                Timestamp::Text(timestamp.with_timezone(&Local).to_rfc3339())
+            }
+            (TimestampFormat::Rfc2822, Some(tz)) => {
+                Timestamp::Text(timestamp.with_timezone(&tz).to_rfc2822())
+            }
+            (TimestampFormat::Rfc2822, None) => {
+                Timestamp::Text(timestamp.with_timezone(&Local).to_rfc2822())
+            }
+        }
+    }
+}
+
+/*  Extracting a `Clock` fails with a `400` if the timezone isn't a known IANA name or the
+    format isn't one of the `TimestampFormat`s.
+*/
+#[async_trait]
+impl<S> FromRequestParts<S> for Clock
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<ClockQuery>::from_request_parts(parts, state).await?;
+
+        let name = match query.tz {
+            Some(name) => Some(name),
+            None => parts
+                .headers
+                .get(TIMEZONE_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        };
+
+        let timezone = match name {
+            Some(name) => Some(
+                name.trim()
+                    .parse::<Tz>()
+                    .map_err(|_| Error::BadRequest(format!("Unknown timezone '{}'", name)))?,
+            ),
+            None => None,
+        };
+
+        Ok(Clock {
+            timezone,
+            format: query.timestamp_format,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn formats_timestamps() {
+        let timestamp = NaiveDate::from_ymd_opt(2014, 11, 28)
+            .unwrap()
+            .and_hms_milli_opt(12, 0, 9, 250)
+            .unwrap()
+            .and_utc();
+        let clock = |timezone: &str, format| Clock {
+            timezone: Some(timezone.parse().unwrap()),
+            format,
+        };
+
+        assert_eq!(
+            clock("America/New_York", TimestampFormat::Rfc3339).format(timestamp),
+            Timestamp::Text("2014-11-28T07:00:09.250-05:00".to_string())
+        );
+        assert_eq!(
+            clock("Asia/Kolkata", TimestampFormat::Rfc2822).format(timestamp),
+            Timestamp::Text("Fri, 28 Nov 2014 17:30:09 +0530".to_string())
+        );
+        assert_eq!(
+            clock("Asia/Kolkata", TimestampFormat::EpochMillis).format(timestamp),
+            Timestamp::Millis(1417176009250)
+        );
+    }
+
+    #[tokio::test]
+    async fn extracts_clock_from_query_and_header() {
+        let extract = |uri: &str, header: Option<&str>| {
+            let mut request = axum::http::Request::builder().uri(uri.to_string());
+            if let Some(header) = header {
+                request = request.header(TIMEZONE_HEADER, header);
+            }
+            let (mut parts, _) = request.body(()).unwrap().into_parts();
+            async move { Clock::from_request_parts(&mut parts, &()).await }
+        };
+
+        let clock = extract("/path?tz=Asia/Tokyo", Some("Europe/Berlin"))
+            .await
+            .unwrap();
+        assert_eq!(clock.timezone, Some(Tz::Asia__Tokyo));
+
+        let clock = extract("/path?timestamp_format=epoch_millis", Some("Europe/Berlin"))
+            .await
+            .unwrap();
+        assert_eq!(clock.timezone, Some(Tz::Europe__Berlin));
+        assert_eq!(clock.format, TimestampFormat::EpochMillis);
+
+        assert_eq!(extract("/path", None).await.unwrap(), Clock::default());
+
+        let error = extract("/path?tz=Mars/Olympus_Mons", None)
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+}
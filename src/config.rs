@@ -0,0 +1,47 @@
+use std::env;
+
+// The largest number of requests in a batch, unless `MAX_BATCH_SIZE` says otherwise
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1000;
+
+// The largest number of jobs running at the same time, unless `MAX_JOBS` says otherwise
+pub const DEFAULT_MAX_JOBS: usize = 4;
+
+/*  `Config` holds the settings of the app which can be changed without building it again,
+    read from the environment when it starts:
+
+    - `MAX_BATCH_SIZE`: the largest number of requests `/path/batch` takes at once
+    - `MAX_JOBS`: the number of workers calculating jobs, i.e. how many of them run at once
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub max_batch_size: usize,
+    pub max_jobs: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_jobs: DEFAULT_MAX_JOBS,
+        }
+    }
+}
+
+impl Config {
+    // Reads the `Config` from the environment, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let defaults = Config::default();
+
+        Config {
+            max_batch_size: env::var("MAX_BATCH_SIZE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.max_batch_size),
+            max_jobs: env::var("MAX_JOBS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|&count| count > 0)
+                .unwrap_or(defaults.max_jobs),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use axum::{
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        FromRequestParts,
+    },
+    http::{HeaderValue, Request as HttpRequest, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json as ResponseJson, Response as HttpResponse},
+};
+use serde_json::{json, Value};
+use sqlx::error::Error as SqlError;
+use std::{fmt, time::Duration};
+use uuid::Uuid;
+
+use crate::validation::Issue;
+
+// The header carrying the correlation id of a request, both ways
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+// The longest a request may take before it's answered with a timeout
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+/*  `Error` is everything that can go wrong while handling a request. Every handler (and
+    every extractor) returns it instead of panicking, so a client always gets a proper
+    status code and a JSON body with the same schema:
+
+    {
+        "error": "Execution 42 not found",
+        "kind": "not_found",
+        "correlation_id": "5f0c6a8e-1e4b-4f43-9d2a-0b3d8c0e6f1a"
+    }
+
+    Some kinds add a field of their own, `validation` errors list their `violations` and
+    `deserialization` errors point to the `line` they happened in (if there is one).
+
+    The correlation id ties the response to our logs, where every error is written to
+    along with it (see `correlate`).
+*/
+#[derive(Debug)]
+pub enum Error {
+    Database(SqlError),
+    Validation(Vec<Issue>),
+    Deserialization {
+        message: String,
+        line: Option<usize>,
+    },
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    NotAcceptable(String),
+    BadRequest(String),
+    NotFound(String),
+    Unprocessable(String),
+    Timeout(Duration),
+    Internal(String),
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::Database(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Deserialization { .. } => StatusCode::BAD_REQUEST,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Database(_) => "database",
+            Error::Validation(_) => "validation",
+            Error::Deserialization { .. } => "deserialization",
+            Error::PayloadTooLarge(_) => "payload_too_large",
+            Error::UnsupportedMediaType(_) => "unsupported_media_type",
+            Error::NotAcceptable(_) => "not_acceptable",
+            Error::BadRequest(_) => "bad_request",
+            Error::NotFound(_) => "not_found",
+            Error::Unprocessable(_) => "unprocessable",
+            Error::Timeout(_) => "timeout",
+            Error::Internal(_) => "internal",
+        }
+    }
+}
+
+/*  The message a client gets to see. Database errors only ever end up in our logs, they
+    might tell a little too much about our setup.
+*/
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Database(_) => write!(f, "The database is currently unavailable"),
+            Error::Validation(_) => write!(f, "Request is invalid"),
+            Error::Deserialization { message, .. } => write!(f, "{}", message),
+            Error::Timeout(duration) => write!(
+                f,
+                "Request didn't finish within {} seconds",
+                duration.as_secs()
+            ),
+            Error::PayloadTooLarge(message)
+            | Error::UnsupportedMediaType(message)
+            | Error::NotAcceptable(message)
+            | Error::BadRequest(message)
+            | Error::NotFound(message)
+            | Error::Unprocessable(message)
+            | Error::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+// The error of the database is kept as the source, so it shows up in our logs.
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<SqlError> for Error {
+    fn from(error: SqlError) -> Self {
+        Error::Database(error)
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Error::Deserialization {
+            message: rejection.to_string(),
+            line: None,
+        }
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(rejection: PathRejection) -> Self {
+        Error::BadRequest(rejection.to_string())
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> HttpResponse {
+        let status = self.status();
+        let correlation_id = correlation_id();
+
+        if status.is_server_error() {
+            tracing::error!(%correlation_id, kind = self.kind(), "{:?}", self);
+        } else {
+            tracing::debug!(%correlation_id, kind = self.kind(), "{}", self);
+        }
+
+        (status, ResponseJson(self.body(correlation_id))).into_response()
+    }
+}
+
+impl Error {
+    // The JSON body of the `Error`, which also shows up in the items of a batch.
+    pub fn body(&self, correlation_id: String) -> Value {
+        let mut body = json!({
+            "error": self.to_string(),
+            "kind": self.kind(),
+            "correlation_id": correlation_id,
+        });
+        match self {
+            Error::Validation(violations) => body["violations"] = json!(violations),
+            Error::Deserialization { line, .. } => body["line"] = json!(line),
+            _ => {}
+        }
+
+        body
+    }
+}
+
+/*  `Query` and `Path` work just like the extractors of `axum` they are built on, but reject
+    a request with an `Error` instead of a plain text message.
+*/
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(Error))]
+pub struct Query<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(Error))]
+pub struct Path<T>(pub T);
+
+/*  The correlation id of the request currently being handled. Outside of a request (e.g.
+    in a test) every call makes up a new one.
+*/
+pub fn correlation_id() -> String {
+    CORRELATION_ID
+        .try_with(|id| id.clone())
+        .unwrap_or_else(|_| Uuid::new_v4().to_string())
+}
+
+/*  This middleware gives every request a correlation id, which is sent back in the
+    `X-Correlation-Id` header of the response (and is part of every `Error`). A client may
+    bring its own one along in the same header (e.g. to follow a request across services),
+    otherwise a new one is made up.
+*/
+pub async fn correlate<B>(request: HttpRequest<B>, next: Next<B>) -> HttpResponse {
+    let id = request
+        .headers()
+        .get(CORRELATION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut response = CORRELATION_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(CORRELATION_HEADER, value);
+    }
+
+    response
+}
+
+/*  This middleware answers requests taking longer than `REQUEST_TIMEOUT` with an `Error`,
+    e.g. when the database doesn't respond or a robot takes too long. The handlers calculate
+    and save on tasks of their own, so they are always waiting for something it can step in
+    on. A simulation given up on is stopped, an `Execution` being saved is saved anyway.
+*/
+pub async fn time_out<B>(request: HttpRequest<B>, next: Next<B>) -> HttpResponse {
+    match tokio::time::timeout(REQUEST_TIMEOUT, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => Error::Timeout(REQUEST_TIMEOUT).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::{Body, HttpBody};
+
+    async fn body(response: HttpResponse) -> Value {
+        let mut body = response.into_body();
+        let mut bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn responds_with_consistent_schema() {
+        let response = CORRELATION_ID
+            .scope("request-1".to_string(), async {
+                Error::NotFound("Execution 42 not found".to_string()).into_response()
+            })
+            .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body(response).await,
+            json!({
+                "error": "Execution 42 not found",
+                "kind": "not_found",
+                "correlation_id": "request-1"
+            })
+        );
+
+        let response = Error::Database(SqlError::PoolTimedOut).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body(response).await;
+        assert_eq!(body["error"], "The database is currently unavailable");
+        assert_eq!(body["kind"], "database");
+        assert!(Uuid::parse_str(body["correlation_id"].as_str().unwrap()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn adds_details_of_kind() {
+        let response = Error::Validation(vec![Issue {
+            path: "$.start.x".to_string(),
+            message: "100001 is outside of the grid".to_string(),
+        }])
+        .into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body(response).await["violations"][0]["path"], "$.start.x");
+
+        let response = Error::Deserialization {
+            message: "invalid type".to_string(),
+            line: Some(3),
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body(response).await["line"], 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_with_error() {
+        let request = HttpRequest::builder()
+            .uri("/executions?limit=many")
+            .body(Body::empty())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+
+        let error = Query::<crate::history::Filter>::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.kind(), "deserialization");
+    }
+}
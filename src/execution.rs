@@ -1,12 +1,25 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use sqlx::{error::Error as SqlError, FromRow, Pool, Postgres};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{error::Error as SqlError, Executor, FromRow, Pool, Postgres};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    time::{Duration, Instant},
+};
 
-use crate::{api::Request, types::Position};
+use crate::{
+    api::Request,
+    error::Error,
+    heatmap::Heatmap,
+    history::Filter,
+    patterns::Plan,
+    timing::{nanos, Timings},
+    trace::{Segment, Trace},
+    types::{Command, Position, Region},
+};
 
-// The amount we have to use to divide seconds in order to get microseconds
-pub const MICROSECONDS: i32 = 1000000;
+// The amount we have to use to divide seconds in order to get nanoseconds
+pub const NANOSECONDS: f64 = 1e9;
 
 /*  The `Execution` model does both, the heavy lifting for the computation as well as
     handling the database interactions. I've explained the `derive` syntax for `Request`
@@ -24,6 +37,25 @@ pub const MICROSECONDS: i32 = 1000000;
     because it's a `float` (where `commands` and `result` can easily be set to `0`).
 
     `timestamp` is kept as UTC here and only ever localized when necessary.
+
+    The remaining fields describe where the robot ended up, how many of the steps it was
+    told to take it actually moved and which part of the grid it has cleaned (`min_*` and
+    `max_*` being the corners of the bounding box). They are "Option"s as well because
+    executions stored before they were introduced don't have them.
+
+    `heatmap` is only stored if the client asked for it, since it can get fairly large.
+    The database keeps it as JSON, which is what the `Json` wrapper is for. The same goes
+    for `trace`, which is stored in the compact text form of a `Trace` though.
+
+    `request` is the request the way the client has sent it, as a `Plan` (as JSON, for
+    auditing and replaying it, with its patterns as they were) along with the `request_hash`
+    of the commands they were expanded into (see `Request::hash`). It's left out when
+    serializing an `Execution`, where it would only repeat the request.
+
+    `replay_of` is the `id` of the `Execution` this one is a replay of, if it is one.
+
+    The `*_nanos` fields are the `Timings` of the phases the request went through, each
+    one in a column of its own so we can tell where latency comes from.
 */
 #[derive(FromRow, Serialize, PartialEq, Debug, Clone, Default)]
 pub struct Execution {
@@ -32,44 +64,296 @@ pub struct Execution {
     pub commands: i32,
     pub result: i32,
     pub duration: Option<f64>,
+    pub position_x: Option<i32>,
+    pub position_y: Option<i32>,
+    pub steps_requested: Option<i64>,
+    pub steps_moved: Option<i64>,
+    pub steps_clamped: Option<i64>,
+    pub min_x: Option<i32>,
+    pub min_y: Option<i32>,
+    pub max_x: Option<i32>,
+    pub max_y: Option<i32>,
+    #[sqlx(default)]
+    pub heatmap: Option<Json<Heatmap>>,
+    #[sqlx(default)]
+    pub trace: Option<String>,
+    pub parse_nanos: Option<i64>,
+    pub simulation_nanos: Option<i64>,
+    pub persistence_nanos: Option<i64>,
+    pub serialization_nanos: Option<i64>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub request: Option<Json<Plan>>,
+    pub request_hash: Option<String>,
+    pub replay_of: Option<i32>,
 }
 
-impl Execution {
-    /*  This is the main function responsible for coordinating the robot's movements
-       and storing the results. In the beginning it simply stores the number of commands
-       it is going to execute, initializes its original position and then builds a HashSet
-       which contain the unique representations of all the fields it has visited.
-
-       Since the HashSet is fairly efficient at storing hashes Positions we can just
-       keep storing positions (it'll essentially be a no-op) regardless of whether they
-       are a part of the set already or not. The members of the set are then the vertices
-       the robot has cleaned, piped into the `result` attribute.
-    */
-    pub async fn calculate(mut self, request: Request) -> Self {
-        self.commands = request.commands.len() as i32;
+/*  A `Simulation` holds everything we learn about the robot's movements while calculating
+    an `Execution` which doesn't end up in the database (and therefore can't be part of the
+    `Execution` itself). It's only ever handed out by `Execution::calculate`.
+*/
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Simulation {
+    pub details: Vec<CommandDetail>,
+    pub visits: HashMap<Position, u32>,
+    pub trace: Trace,
+}
+
+/*  A `CommandDetail` is the breakdown of what a single `Command` did: where the robot
+    started from and where it ended up, how many vertices it has cleaned for the first time
+    (`cleaned`), how many it has passed over again (`recleaned`) and how many of its steps
+    were dropped at the edge of the grid (`clamped`). It's only ever calculated, never stored.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct CommandDetail {
+    pub start: Position,
+    pub end: Position,
+    pub cleaned: i64,
+    pub recleaned: i64,
+    pub clamped: i64,
+}
+
+/*  `Progress` is how far a simulation has come after a command: the number of commands it
+    has completed (out of `commands`), where the robot is, how many vertices it has cleaned
+    so far and the `percent` of the steps it has taken (or dropped at the edge of the grid).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct Progress {
+    pub command: usize,
+    pub commands: usize,
+    pub position: Position,
+    pub cleaned: i64,
+    pub percent: f64,
+}
+
+/*  A `Robot` is a simulation in progress, taking one `Command` after the other. It starts
+    at its original position and builds a HashMap which contains the unique representations
+    of all the fields it has visited, together with the number of times it has visited them.
+
+    Since the HashMap is fairly efficient at storing hashes Positions we can just keep
+    storing positions (for a known one it'll essentially just increase its counter)
+    regardless of whether they are a part of the map already or not. The keys of the map
+    are then the vertices the robot has cleaned, piped into the `result` attribute, and the
+    counters make up the `Heatmap`. A step dropped at the edge of the grid counts as another
+    visit of the vertex the robot is stuck on.
+
+    Along the way it counts the steps the robot could actually take (a step which didn't
+    change the `Position` was dropped at the edge of the grid) and keeps track of the
+    bounding box of the cleaned vertices. For every command it also records a
+    `CommandDetail`, making it possible to tell which command has been inefficient, and the
+    `Segment` the robot has driven for the `Trace`.
+
+    It remembers the commands it has executed, so once it's done it can tell the whole
+    `Request` it has run. Only the time spent on executing commands counts towards its
+    `duration`.
+*/
+#[derive(Debug, Clone)]
+pub struct Robot {
+    start: Position,
+    position: Position,
+    commands: Vec<Command>,
+    simulation: Simulation,
+    bounds: Option<Region>,
+    steps_requested: i64,
+    steps_moved: i64,
+    elapsed: Duration,
+}
+
+impl Robot {
+    pub fn new(start: Position) -> Self {
+        Robot {
+            start,
+            position: start,
+            commands: vec![],
+            simulation: Simulation::default(),
+            bounds: None,
+            steps_requested: 0,
+            steps_moved: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
 
-        let mut position = request.start;
-        let mut cleaned: HashSet<Position> = HashSet::new();
-        let commands = request.commands;
+    // The number of vertices cleaned so far.
+    pub fn cleaned(&self) -> i64 {
+        self.simulation.visits.len() as i64
+    }
+
+    // The number of commands executed so far.
+    pub fn commands(&self) -> usize {
+        self.commands.len()
+    }
 
-        /*  This is our starting timestamp for measuring the duration
+    // Moves the robot as the `Command` says, returning what it did.
+    pub fn execute(&mut self, command: Command) -> &CommandDetail {
+        /*  This is our starting point for measuring the duration
             of the computation.
         */
-        let start_time = Utc::now();
-
-        for command in commands {
-            /*  This creates an _inclusive_ Range type in Rust, in this case
-                1 to "number of steps".
-            */
-            for _ in 1..=command.steps {
-                position = position.shift(&command.direction);
-                cleaned.insert(position);
+        let start_time = Instant::now();
+        let steps = command.steps.max(0) as i64;
+        let mut position = self.position;
+        let cleaned = &mut self.simulation.visits;
+        let mut detail = CommandDetail {
+            start: position,
+            end: position,
+            cleaned: 0,
+            recleaned: 0,
+            clamped: 0,
+        };
+
+        /*  This creates an _inclusive_ Range type in Rust, in this case
+            1 to "number of steps".
+        */
+        for _ in 1..=command.steps {
+            let destination = position.shift(&command.direction);
+            let moved = destination != position;
+            position = destination;
+
+            match cleaned.entry(position) {
+                Entry::Vacant(entry) => {
+                    entry.insert(1);
+                    detail.cleaned += 1;
+                    self.bounds = Some(match self.bounds {
+                        Some(region) => region.including(position),
+                        None => Region {
+                            min: position,
+                            max: position,
+                        },
+                    });
+                }
+                Entry::Occupied(mut entry) => {
+                    *entry.get_mut() += 1;
+                    if moved {
+                        detail.recleaned += 1;
+                    }
+                }
+            }
+
+            if !moved {
+                detail.clamped += 1;
             }
         }
 
-        self = self.set_duration(start_time);
-        self.result = cleaned.len() as i32;
-        self
+        detail.end = position;
+        self.position = position;
+        self.steps_requested += steps;
+        self.steps_moved += steps - detail.clamped;
+        self.simulation.trace.push(Segment {
+            from: detail.start,
+            to: detail.end,
+            direction: command.direction.clone(),
+            moved: steps - detail.clamped,
+            clamped: detail.clamped,
+        });
+        self.simulation.details.push(detail);
+        self.commands.push(command);
+        self.elapsed += start_time.elapsed();
+
+        &self.simulation.details[self.simulation.details.len() - 1]
+    }
+
+    /*  Fills in the `Execution` with the results of the robot (and the `Request` it has
+        run), handing out the `Simulation` along with it.
+    */
+    pub fn finish(self, execution: Execution) -> (Execution, Simulation) {
+        let request = Request {
+            start: self.start,
+            commands: self.commands,
+        };
+        let bounds = self.bounds;
+
+        let mut execution = execution.set_duration(self.elapsed);
+        execution.commands = request.commands.len() as i32;
+        execution.request_hash = Some(request.hash());
+        execution.request = Some(Json(Plan::from(request)));
+        execution.result = self.simulation.visits.len() as i32;
+        execution.position_x = Some(self.position.x);
+        execution.position_y = Some(self.position.y);
+        execution.steps_requested = Some(self.steps_requested);
+        execution.steps_moved = Some(self.steps_moved);
+        execution.steps_clamped = Some(self.steps_requested - self.steps_moved);
+        execution.min_x = bounds.map(|b| b.min.x);
+        execution.min_y = bounds.map(|b| b.min.y);
+        execution.max_x = bounds.map(|b| b.max.x);
+        execution.max_y = bounds.map(|b| b.max.y);
+        (execution, self.simulation)
+    }
+}
+
+impl Execution {
+    /*  This is the main function responsible for coordinating the robot's movements and
+       storing the results: it expands the `Plan` into plain commands, hands every one of
+       them to a `Robot` (which is where the actual cleaning happens) and fills in the
+       `Execution` once it's done, keeping the `Plan` as its `request`. The `Simulation` is
+       handed out next to it, for callers interested in more than the results we are storing.
+
+       After every command the `observer` is told about the `Progress` and decides whether
+       the robot goes on (`true`) or stops right there (`false`), in which case there's no
+       `Execution` at all (e.g. when a job has been cancelled). Callers who want it to run
+       to the end simply pass `|_| true`.
+
+       It doesn't wait for anything, it keeps the CPU busy for as long as it takes, which
+       is why callers with large requests run it on a thread of its own (e.g. with
+       `tokio::task::spawn_blocking`), where it doesn't hold up any other requests.
+    */
+    pub fn calculate(
+        self,
+        plan: impl Into<Plan>,
+        mut observer: impl FnMut(&Progress) -> bool,
+    ) -> Option<(Self, Simulation)> {
+        let plan = plan.into();
+        let request = Request::from(plan.clone());
+        let total: i64 = request.commands.iter().map(|c| c.steps.max(0) as i64).sum();
+        let count = request.commands.len();
+        let mut robot = Robot::new(request.start);
+
+        for (index, command) in request.commands.into_iter().enumerate() {
+            robot.execute(command);
+
+            let progress = Progress {
+                command: index + 1,
+                commands: count,
+                position: robot.position(),
+                cleaned: robot.cleaned(),
+                percent: match total {
+                    0 => 100.0,
+                    total => robot.steps_requested as f64 * 100.0 / total as f64,
+                },
+            };
+            if !observer(&progress) {
+                return None;
+            }
+        }
+
+        let (mut execution, simulation) = robot.finish(self);
+        execution.request = Some(Json(plan));
+
+        Some((execution, simulation))
+    }
+
+    // The final `Position` of the robot, if the execution has one.
+    pub fn position(&self) -> Option<Position> {
+        Some(Position {
+            x: self.position_x?,
+            y: self.position_y?,
+        })
+    }
+
+    // The bounding box of the vertices the robot has cleaned, if it has cleaned any.
+    pub fn bounds(&self) -> Option<Region> {
+        Some(Region {
+            min: Position {
+                x: self.min_x?,
+                y: self.min_y?,
+            },
+            max: Position {
+                x: self.max_x?,
+                y: self.max_y?,
+            },
+        })
     }
 
     /*  This function contains the interaction logic for the persistence layer/database.
@@ -84,29 +368,121 @@ impl Execution {
         places you would want to have proper error handling at in the future.
     */
     pub async fn save(&self, state: Pool<Postgres>) -> Result<Execution, SqlError> {
+        self.save_with(&state).await
+    }
+
+    /*  `save` with anything that can run a query, e.g. a `Transaction` for saving several
+        executions at once.
+    */
+    pub async fn save_with<'e, E>(&self, executor: E) -> Result<Execution, SqlError>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let result: Execution = sqlx::query_as(
-        r#"insert into executions (commands, result, duration) values ($1, $2, $3) returning *"#,
-    )
-    .bind(self.commands)
-    .bind(self.result)
-    .bind(self.duration.clone()).fetch_one(&state)
-    .await?;
+            r#"insert into executions (
+                commands, result, duration, position_x, position_y, steps_requested,
+                steps_moved, steps_clamped, min_x, min_y, max_x, max_y, heatmap, trace,
+                parse_nanos, simulation_nanos, request, request_hash, replay_of
+            ) values (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19
+            ) returning *"#,
+        )
+        .bind(self.commands)
+        .bind(self.result)
+        .bind(self.duration)
+        .bind(self.position_x)
+        .bind(self.position_y)
+        .bind(self.steps_requested)
+        .bind(self.steps_moved)
+        .bind(self.steps_clamped)
+        .bind(self.min_x)
+        .bind(self.min_y)
+        .bind(self.max_x)
+        .bind(self.max_y)
+        .bind(&self.heatmap)
+        .bind(&self.trace)
+        .bind(self.parse_nanos)
+        .bind(self.simulation_nanos)
+        .bind(&self.request)
+        .bind(&self.request_hash)
+        .bind(self.replay_of)
+        .fetch_one(executor)
+        .await?;
 
         Ok(result)
     }
 
-    /*  This function take the initial timestamp we saved before triggering the
-        calculation of the robot movements and compares it against a current timestamp.
-        It then takes the microseconds elapsed between then and now and converts them
-        into a seconds-precision float representation, as required by the data model from
-        the challenge.
+    /*  Some phases only end once the `Execution` has been saved (persisting it, obviously,
+        and serializing the `Response` built from it), so their `Timings` are recorded in
+        the database afterwards.
     */
-    fn set_duration(mut self, start_time: DateTime<Utc>) -> Self {
-        let now = Utc::now();
-        let duration = (now - start_time)
-            .num_microseconds()
-            .map(|d| d as f64 / MICROSECONDS as f64);
-        self.duration = duration;
+    pub async fn record_timings<'e, E>(
+        executor: E,
+        id: i32,
+        timings: Timings,
+    ) -> Result<(), SqlError>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query(
+            r#"update executions set persistence_nanos = $2, serialization_nanos = $3
+            where id = $1"#,
+        )
+        .bind(id)
+        .bind(timings.persistence)
+        .bind(timings.serialization)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /*  Fetches a previously saved `Execution` by its `id`. Since there might not be one
+        with the given `id` the result is an "Option" (inside of the `Result` for any
+        errors the database might run into).
+    */
+    pub async fn find(state: Pool<Postgres>, id: i32) -> Result<Option<Execution>, SqlError> {
+        let result: Option<Execution> = sqlx::query_as(r#"select * from executions where id = $1"#)
+            .bind(id)
+            .fetch_optional(&state)
+            .await?;
+
+        Ok(result)
+    }
+
+    /*  Fetches a page of stored executions, as described by the `Filter` (see
+        `Filter::select`), without their `heatmap`, `trace` and `request`. The `Filter`
+        might not make sense (e.g. because of a broken cursor), which is an `Error` of
+        its own.
+    */
+    pub async fn list(state: Pool<Postgres>, filter: &Filter) -> Result<Vec<Execution>, Error> {
+        let mut query = filter.select().map_err(Error::BadRequest)?;
+        let result = query.build_query_as().fetch_all(&state).await?;
+
+        Ok(result)
+    }
+
+    // The `Timings` recorded for this `Execution`, if there are any (see `Timings`).
+    pub fn timings(&self) -> Option<Timings> {
+        let timings = Timings {
+            parse: self.parse_nanos,
+            simulation: self.simulation_nanos,
+            persistence: self.persistence_nanos,
+            serialization: self.serialization_nanos,
+        };
+
+        (timings != Timings::default()).then_some(timings)
+    }
+
+    /*  This function takes the time elapsed on the monotonic clock since we started the
+        calculation of the robot movements. It stores the nanoseconds as the `simulation`
+        phase and converts them into a seconds-precision float representation for the
+        `duration`, as required by the data model from the challenge.
+    */
+    fn set_duration(mut self, elapsed: Duration) -> Self {
+        self.simulation_nanos = Some(nanos(elapsed));
+        self.duration = Some(elapsed.as_nanos() as f64 / NANOSECONDS);
         self
     }
 }
@@ -119,10 +495,24 @@ impl Execution {
 mod test {
     use crate::{
         api::Request,
-        types::{Command, Direction, Position},
+        types::{Command, Direction, Position, Region},
     };
 
-    use super::Execution;
+    use super::{CommandDetail, Execution, Progress};
+    use crate::render::ascii;
+
+    /*  Runs a `Request` and also returns a text map of the robot's path (see
+        `render::ascii`), which makes failing assertions a lot easier to read.
+    */
+    async fn calculate_with_map(request: Request) -> (Execution, String) {
+        let start = request.start;
+        let (execution, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let map = ascii::render(start, &simulation).unwrap_or_default();
+
+        (execution, map)
+    }
 
     #[tokio::test]
     async fn calculates_row_item_10_east() {
@@ -134,9 +524,8 @@ mod test {
             }],
         };
 
-        let execution = Execution::default();
-        let execution = execution.calculate(request).await;
-        assert_eq!(execution.result, 10)
+        let (execution, map) = calculate_with_map(request).await;
+        assert_eq!(execution.result, 10, "\n{}", map)
     }
 
     #[tokio::test]
@@ -149,9 +538,8 @@ mod test {
             }],
         };
 
-        let execution = Execution::default();
-        let execution = execution.calculate(request).await;
-        assert_eq!(execution.result, 0)
+        let (execution, map) = calculate_with_map(request).await;
+        assert_eq!(execution.result, 0, "\n{}", map)
     }
 
     #[tokio::test]
@@ -164,9 +552,8 @@ mod test {
             }],
         };
 
-        let execution = Execution::default();
-        let execution = execution.calculate(request).await;
-        assert_eq!(execution.result, 10)
+        let (execution, map) = calculate_with_map(request).await;
+        assert_eq!(execution.result, 10, "\n{}", map)
     }
 
     #[tokio::test]
@@ -185,9 +572,8 @@ mod test {
             ],
         };
 
-        let execution = Execution::default();
-        let execution = execution.calculate(request).await;
-        assert_eq!(execution.result, 11)
+        let (execution, map) = calculate_with_map(request).await;
+        assert_eq!(execution.result, 11, "\n{}", map)
     }
 
     #[tokio::test]
@@ -210,9 +596,8 @@ mod test {
             ],
         };
 
-        let execution = Execution::default();
-        let execution = execution.calculate(request).await;
-        assert_eq!(execution.result, 144)
+        let (execution, map) = calculate_with_map(request).await;
+        assert_eq!(execution.result, 144, "\n{}", map)
     }
 
     #[tokio::test]
@@ -236,7 +621,212 @@ mod test {
         };
 
         let execution = Execution::default();
-        let execution = execution.calculate(request).await;
+        let execution = execution.calculate(request, |_| true).unwrap_or_default().0;
         assert_eq!(execution.result, 99849)
     }
+
+    #[tokio::test]
+    async fn calculates_statistics_at_boundary() {
+        let request = Request {
+            start: Position { x: 99998, y: 5 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 5,
+                },
+                Command {
+                    direction: Direction::South,
+                    steps: 3,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: -4,
+                },
+            ],
+        };
+
+        let execution = Execution::default();
+        let execution = execution.calculate(request, |_| true).unwrap_or_default().0;
+        assert_eq!(execution.position(), Some(Position { x: 100000, y: 2 }));
+        assert_eq!(execution.steps_requested, Some(8));
+        assert_eq!(execution.steps_moved, Some(5));
+        assert_eq!(execution.steps_clamped, Some(3));
+        assert_eq!(
+            execution.bounds(),
+            Some(Region {
+                min: Position { x: 99999, y: 2 },
+                max: Position { x: 100000, y: 5 },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn calculates_command_details() {
+        let request = Request {
+            start: Position { x: 99998, y: 0 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 4,
+                },
+                Command {
+                    direction: Direction::West,
+                    steps: 3,
+                },
+            ],
+        };
+
+        let execution = Execution::default();
+        let (_, simulation) = execution.calculate(request, |_| true).unwrap_or_default();
+        assert_eq!(
+            simulation.details,
+            vec![
+                CommandDetail {
+                    start: Position { x: 99998, y: 0 },
+                    end: Position { x: 100000, y: 0 },
+                    cleaned: 2,
+                    recleaned: 0,
+                    clamped: 2,
+                },
+                CommandDetail {
+                    start: Position { x: 100000, y: 0 },
+                    end: Position { x: 99997, y: 0 },
+                    cleaned: 2,
+                    recleaned: 1,
+                    clamped: 0,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_visits() {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 2,
+                },
+                Command {
+                    direction: Direction::West,
+                    steps: 2,
+                },
+                Command {
+                    direction: Direction::East,
+                    steps: 1,
+                },
+            ],
+        };
+
+        let execution = Execution::default();
+        let (execution, simulation) = execution.calculate(request, |_| true).unwrap_or_default();
+        assert_eq!(execution.result, 3);
+        assert_eq!(simulation.visits[&Position { x: 0, y: 0 }], 1);
+        assert_eq!(simulation.visits[&Position { x: 1, y: 0 }], 3);
+        assert_eq!(simulation.visits[&Position { x: 2, y: 0 }], 1);
+    }
+
+    #[tokio::test]
+    async fn calculates_no_bounds_without_steps() {
+        let request = Request {
+            start: Position { x: 3, y: 4 },
+            commands: vec![],
+        };
+
+        let execution = Execution::default();
+        let execution = execution.calculate(request, |_| true).unwrap_or_default().0;
+        assert_eq!(execution.position(), Some(Position { x: 3, y: 4 }));
+        assert_eq!(execution.bounds(), None);
+    }
+
+    #[test]
+    fn reports_progress_and_stops_when_told_to() {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 3,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 1,
+                },
+            ],
+        };
+
+        let mut reported = vec![];
+        let result = Execution::default().calculate(request.clone(), |progress| {
+            reported.push(*progress);
+            true
+        });
+        assert_eq!(result.map(|(execution, _)| execution.result), Some(4));
+        assert_eq!(
+            reported[0],
+            Progress {
+                command: 1,
+                commands: 2,
+                position: Position { x: 3, y: 0 },
+                cleaned: 3,
+                percent: 75.0,
+            }
+        );
+        assert_eq!(reported[1].percent, 100.0);
+
+        let mut commands = 0;
+        let result = Execution::default().calculate(request, |_| {
+            commands += 1;
+            false
+        });
+        assert!(result.is_none());
+        assert_eq!(commands, 1);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn lists_executions_without_heavy_columns(pool: sqlx::PgPool) {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![Command {
+                direction: Direction::East,
+                steps: 2,
+            }],
+        };
+        let (mut execution, _) = Execution::default()
+            .calculate(request.clone(), |_| true)
+            .unwrap_or_default();
+        execution.trace = Some("0,0 E2".to_string());
+        let saved = execution.save(pool.clone()).await.unwrap();
+        assert!(saved.request.is_some() && saved.trace.is_some());
+
+        let listed = Execution::list(pool, &crate::history::Filter::default())
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0].id, listed[0].result), (saved.id, 2));
+        assert_eq!(listed[0].request_hash, Some(request.hash()));
+        assert!(listed[0].request.is_none() && listed[0].trace.is_none());
+    }
+
+    #[test]
+    fn keeps_plan_as_sent() {
+        use crate::patterns::{Instruction, Pattern, Plan};
+
+        let start = Position { x: 0, y: 0 };
+        let plan = Plan {
+            start,
+            commands: vec![Instruction::Pattern(Pattern::Spot {
+                center: start,
+                radius: 1,
+            })],
+        };
+
+        let (execution, _) = Execution::default()
+            .calculate(plan.clone(), |_| true)
+            .unwrap();
+        assert_eq!(execution.result, 8);
+        assert_eq!(execution.request.unwrap().0, plan.clone());
+        assert_eq!(execution.request_hash, Some(Request::from(plan).hash()));
+    }
 }
@@ -0,0 +1,435 @@
+use axum::{
+    async_trait,
+    body::{Bytes, HttpBody},
+    extract::{FromRequest, FromRequestParts},
+    http::{header, request::Parts, Request, StatusCode},
+    response::{IntoResponse, Response as HttpResponse},
+    BoxError,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+
+/*  Not every client wants to read JSON: our data pipeline and embedded clients prefer
+    compact binary formats, and analysts like to open results in a spreadsheet. A `Format`
+    is one of the representations we can send, chosen by the `Accept` header of a request.
+
+    All of them are generated from the same `serde` serialization, which is why they all
+    carry the same fields.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+    Csv,
+}
+
+impl Format {
+    /*  Picks the `Format` for the value of an `Accept` header, e.g.
+        `application/cbor, application/json;q=0.5`. Media types are tried in the order
+        of their quality (`q`), the first one we support wins. No `Accept` header at all
+        means the client takes anything, which is JSON.
+    */
+    pub fn negotiate(accept: Option<&str>) -> Option<Format> {
+        let accept = match accept {
+            Some(accept) if !accept.trim().is_empty() => accept,
+            _ => return Some(Format::Json),
+        };
+
+        let mut ranges: Vec<(&str, f32)> = accept
+            .split(',')
+            .map(|range| {
+                let mut parameters = range.split(';').map(str::trim);
+                let media_type = parameters.next().unwrap_or_default();
+                let quality = parameters
+                    .find_map(|parameter| parameter.strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                (media_type, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+
+        // A stable sort keeps the order of the header for media types of the same quality
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges
+            .into_iter()
+            .find_map(|(media_type, _)| Format::from_media_type(media_type))
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            "application/cbor" => Some(Format::Cbor),
+            "text/csv" | "text/*" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    // Serializes `value` into the `Format`.
+    pub fn encode<T: Serialize + Columns>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Cbor => {
+                let mut bytes = vec![];
+                ciborium::ser::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+                Ok(bytes)
+            }
+            Format::Csv => csv(value),
+        }
+    }
+}
+
+/*  With this `Format` becomes an extractor: a handler can simply take a `Format` as
+    one of its arguments. If the client doesn't accept any of our formats the request is
+    rejected with a `406` before the handler even runs.
+*/
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accept = parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok());
+
+        Format::negotiate(accept).ok_or_else(|| {
+            Error::NotAcceptable(
+                "Supported formats are application/json, application/msgpack, application/cbor and text/csv"
+                    .to_string(),
+            )
+        })
+    }
+}
+
+/*  `Negotiated` wraps anything we want to send back to the client in the `Format` it has
+    asked for, along with the matching `content-type`.
+*/
+pub struct Negotiated<T>(pub Format, pub T);
+
+impl<T: Serialize + Columns> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> HttpResponse {
+        let Negotiated(format, value) = self;
+
+        match format.encode(&value) {
+            Ok(bytes) => ([(header::CONTENT_TYPE, format.content_type())], bytes).into_response(),
+            Err(error) => Error::Internal(error).into_response(),
+        }
+    }
+}
+
+/*  The other way around our operators keep their cleaning plans in YAML and TOML files,
+    so besides JSON a request body can be in either of these. The `Syntax` of a body is
+    chosen by its `content-type` header.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Syntax {
+    pub fn from_content_type(content_type: &str) -> Option<Syntax> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" => Some(Syntax::Json),
+            "application/yaml" | "application/x-yaml" | "text/yaml" => Some(Syntax::Yaml),
+            "application/toml" => Some(Syntax::Toml),
+            _ => None,
+        }
+    }
+
+    /*  Deserializes a body in this `Syntax`. Each of the parsers has its own way of
+        telling where it went wrong, but all of them know the line, which is returned
+        along with the error message (if there is one, that is).
+    */
+    pub fn parse<T: DeserializeOwned>(&self, body: &[u8]) -> Result<T, (String, Option<usize>)> {
+        match self {
+            Syntax::Json => {
+                serde_json::from_slice(body).map_err(|e| (e.to_string(), Some(e.line())))
+            }
+            Syntax::Yaml => serde_yaml::from_slice(body)
+                .map_err(|e| (e.to_string(), e.location().map(|l| l.line()))),
+            Syntax::Toml => {
+                let text = std::str::from_utf8(body).map_err(|e| (e.to_string(), None))?;
+                toml::from_str(text).map_err(|e| {
+                    let line = e
+                        .span()
+                        .map(|span| text[..span.start].matches('\n').count() + 1);
+                    (e.message().to_string(), line)
+                })
+            }
+        }
+    }
+}
+
+/*  `Payload` is what handlers use instead of `Json` for their request bodies, accepting
+    any `Syntax`. Bodies with any other `content-type` are rejected with a `415`, bodies
+    which can't be parsed with a `400`, pointing to the offending line (see `Error`).
+*/
+pub struct Payload<T>(pub T);
+
+#[async_trait]
+impl<S, B, T> FromRequest<S, B> for Payload<T>
+where
+    T: DeserializeOwned,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let syntax = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Syntax::from_content_type)
+            .ok_or_else(|| {
+                Error::UnsupportedMediaType(
+                    "Supported request formats are application/json, application/yaml and application/toml"
+                        .to_string(),
+                )
+            })?;
+
+        let body = Bytes::from_request(request, state).await.map_err(|e| {
+            let message = e.to_string();
+            match e.into_response().status() {
+                StatusCode::PAYLOAD_TOO_LARGE => Error::PayloadTooLarge(message),
+                _ => Error::Deserialization {
+                    message,
+                    line: None,
+                },
+            }
+        })?;
+
+        syntax
+            .parse(&body)
+            .map(Payload)
+            .map_err(|(message, line)| Error::Deserialization { message, line })
+    }
+}
+
+/*  CSV is the odd one out, since it only knows about flat rows of text. Nested objects
+    are flattened into columns named after their path (e.g. `position_x`), anything else
+    which doesn't fit into a single cell (i.e. arrays) is written as JSON. A list of values
+    becomes one row per value, everything else a single row.
+
+    A spreadsheet (or a script) expects the same columns in the same order every time, no
+    matter which fields happen to be `null`, which is why they are taken from the `Columns`
+    of the type rather than from the data. Fields which are missing or `null` leave their
+    cells empty.
+*/
+fn csv<T: Serialize + Columns>(value: &T) -> Result<Vec<u8>, String> {
+    let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    let values = match value {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+
+    let columns = T::columns();
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&columns).map_err(|e| e.to_string())?;
+    for value in &values {
+        let mut row = Map::new();
+        flatten("", value, &mut row);
+
+        let record = columns.iter().map(|column| match row.get(column) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(text)) => text.clone(),
+            Some(value) => value.to_string(),
+        });
+        writer.write_record(record).map_err(|e| e.to_string())?;
+    }
+
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+/*  The columns of the CSV a type is sent as (see `csv`), in order. Everything sent as a
+    `Negotiated` value has to list them, a list of values has the same columns as each of
+    its values.
+*/
+pub trait Columns {
+    fn columns() -> Vec<String>;
+}
+
+impl<T: Columns> Columns for Vec<T> {
+    fn columns() -> Vec<String> {
+        T::columns()
+    }
+}
+
+// Turns a list of names into `Columns`.
+pub fn columns(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+// Prefixes `Columns` with the path of their parent, e.g. `min_x` becomes `bounds_min_x`.
+pub fn prefixed(prefix: &str, columns: Vec<String>) -> Vec<String> {
+    columns
+        .into_iter()
+        .map(|column| format!("{}_{}", prefix, column))
+        .collect()
+}
+
+pub fn flatten(prefix: &str, value: &Value, row: &mut Map<String, Value>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let column = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}_{}", prefix, key)
+                };
+                flatten(&column, value, row);
+            }
+        }
+        value => {
+            row.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{Request, Response},
+        execution::Execution,
+    };
+
+    #[test]
+    fn parses_yaml_and_toml_requests() {
+        let json: Request = Syntax::Json
+            .parse(include_bytes!(
+                "../test/fixtures/example_request_positive.json"
+            ))
+            .unwrap();
+
+        let yaml = "start: {x: 10, y: 22}\ncommands:\n  - direction: east\n    steps: 2\n  - direction: north\n    steps: 1\n";
+        assert_eq!(
+            Syntax::from_content_type("application/yaml; charset=utf-8"),
+            Some(Syntax::Yaml)
+        );
+        assert_eq!(
+            Syntax::Yaml.parse::<Request>(yaml.as_bytes()).unwrap(),
+            json
+        );
+
+        let toml = "start = { x = 10, y = 22 }\n\n[[commands]]\ndirection = \"east\"\nsteps = 2\n\n[[commands]]\ndirection = \"north\"\nsteps = 1\n";
+        assert_eq!(
+            Syntax::Toml.parse::<Request>(toml.as_bytes()).unwrap(),
+            json
+        );
+    }
+
+    #[test]
+    fn points_to_offending_line() {
+        let yaml = "start: {x: 10, y: 22}\ncommands:\n  - direction: up\n    steps: 2\n";
+        let (_, line) = Syntax::Yaml.parse::<Request>(yaml.as_bytes()).unwrap_err();
+        assert_eq!(line, Some(3));
+
+        let toml = "start = { x = 10, y = 22 }\ncommands = []\nsteps = = 3\n";
+        let (_, line) = Syntax::Toml.parse::<Request>(toml.as_bytes()).unwrap_err();
+        assert_eq!(line, Some(3));
+
+        let json = "{\n  \"start\": {\"x\": 10, \"y\": \"22\"},\n  \"commands\": []\n}";
+        let (_, line) = Syntax::Json.parse::<Request>(json.as_bytes()).unwrap_err();
+        assert_eq!(line, Some(2));
+    }
+
+    #[test]
+    fn negotiates_formats() {
+        assert_eq!(Format::negotiate(None), Some(Format::Json));
+        assert_eq!(Format::negotiate(Some("*/*")), Some(Format::Json));
+        assert_eq!(
+            Format::negotiate(Some("application/x-msgpack")),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(
+            Format::negotiate(Some("application/json;q=0.5, application/cbor")),
+            Some(Format::Cbor)
+        );
+        assert_eq!(
+            Format::negotiate(Some("text/html, text/csv;q=0.1")),
+            Some(Format::Csv)
+        );
+        assert_eq!(Format::negotiate(Some("text/html")), None);
+        assert_eq!(Format::negotiate(Some("application/cbor;q=0")), None);
+    }
+
+    fn response() -> Response {
+        Response::from(Execution {
+            id: Some(7),
+            commands: 2,
+            result: 3,
+            duration: Some(0.000001),
+            position_x: Some(12),
+            position_y: Some(23),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn encodes_binary_formats_with_same_fields() {
+        let json = serde_json::to_value(response()).unwrap();
+
+        let bytes = Format::MessagePack.encode(&response()).unwrap();
+        let decoded: Response = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(serde_json::to_value(decoded).unwrap(), json);
+
+        let bytes = Format::Cbor.encode(&response()).unwrap();
+        let decoded: Response = ciborium::de::from_reader(&bytes[..]).unwrap();
+        assert_eq!(serde_json::to_value(decoded).unwrap(), json);
+    }
+
+    #[test]
+    fn encodes_csv_with_flattened_columns() {
+        let csv = String::from_utf8(Format::Csv.encode(&response()).unwrap()).unwrap();
+
+        assert_eq!(
+            csv,
+            format!(
+                "{}\n7,,2,3,0.000001,12,23{}\n",
+                Response::columns().join(","),
+                ",".repeat(Response::columns().len() - 7)
+            )
+        );
+        assert!(csv.starts_with(
+            "id,timestamp,commands,result,duration,position_x,position_y,\
+             steps_requested,steps_moved,steps_clamped,\
+             bounds_min_x,bounds_min_y,bounds_max_x,bounds_max_y,"
+        ));
+
+        let mut bounded = serde_json::to_value(response()).unwrap();
+        bounded["bounds"] = serde_json::json!({"min": {"x": 0, "y": 0}, "max": {"x": 12, "y": 23}});
+        let bounded: Response = serde_json::from_value(bounded).unwrap();
+
+        let csv =
+            String::from_utf8(Format::Csv.encode(&vec![response(), bounded]).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], Response::columns().join(","));
+        assert!(lines[2].starts_with("7,,2,3,0.000001,12,23,,,,0,0,12,23,"));
+    }
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::types::Position;
+
+/*  A `HashSet` of cleaned vertices can't tell how often the robot has passed over each of
+    them, which is why the simulation counts its visits per vertex instead. A `Heatmap` is
+    the sparse representation of these counts we hand out and store.
+
+    The vertices are sorted row by row (bottom to top, left to right) and neighbouring
+    vertices of a row with the same number of visits are combined into a single `Run`.
+    A robot going back and forth along the same lanes therefore only yields a handful of
+    `Run`s, no matter how long the lanes are.
+
+    On top of that it carries a few summary statistics: the `max`imum and `mean` number of
+    visits per vertex, and the share of vertices which were visited more than once
+    (`revisited`, between `0` and `1`).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Heatmap {
+    pub max: u32,
+    pub mean: f64,
+    pub revisited: f64,
+    pub runs: Vec<Run>,
+}
+
+/*  A `Run` is a horizontal line of `length` vertices, starting at `x`/`y` and going east,
+    all of which were visited the same number of times. Being a tuple struct it gets
+    serialized as a plain array (`[x, y, length, visits]`), which keeps the output compact.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Run(pub i32, pub i32, pub u32, pub u32);
+
+impl From<&HashMap<Position, u32>> for Heatmap {
+    fn from(visits: &HashMap<Position, u32>) -> Self {
+        if visits.is_empty() {
+            return Heatmap::default();
+        }
+
+        let runs = runs(visits, |count| count);
+        let cells = visits.len() as f64;
+        let total: u64 = visits.values().map(|v| *v as u64).sum();
+
+        Heatmap {
+            max: visits.values().copied().max().unwrap_or_default(),
+            mean: total as f64 / cells,
+            revisited: visits.values().filter(|v| **v > 1).count() as f64 / cells,
+            runs,
+        }
+    }
+}
+
+/*  Combines the vertices of `visits` into `Run`s, row by row (bottom to top, left to right).
+    Neighbouring vertices end up in the same `Run` if `weight` yields the same number for
+    their visits, which is what the `Run` carries as its visits, e.g. `|_| 1` combines all
+    neighbouring vertices, no matter how often they were visited.
+*/
+pub fn runs(visits: &HashMap<Position, u32>, weight: impl Fn(u32) -> u32) -> Vec<Run> {
+    let mut vertices: Vec<(&Position, u32)> = visits
+        .iter()
+        .map(|(position, count)| (position, weight(*count)))
+        .collect();
+    vertices.sort_unstable_by_key(|(position, _)| (position.y, position.x));
+
+    let mut runs: Vec<Run> = vec![];
+    for (position, count) in vertices {
+        match runs.last_mut() {
+            Some(Run(x, y, length, visits))
+                if *y == position.y
+                    && *x as i64 + *length as i64 == position.x as i64
+                    && *visits == count =>
+            {
+                *length += 1
+            }
+            _ => runs.push(Run(position.x, position.y, 1, count)),
+        }
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encodes_runs_and_statistics() {
+        let visits: HashMap<Position, u32> = [
+            ((0, 0), 1),
+            ((1, 0), 1),
+            ((2, 0), 3),
+            ((4, 0), 3),
+            ((0, 1), 2),
+            ((1, 1), 2),
+        ]
+        .into_iter()
+        .map(|((x, y), count)| (Position { x, y }, count))
+        .collect();
+
+        let heatmap = Heatmap::from(&visits);
+
+        assert_eq!(
+            heatmap.runs,
+            vec![
+                Run(0, 0, 2, 1),
+                Run(2, 0, 1, 3),
+                Run(4, 0, 1, 3),
+                Run(0, 1, 2, 2)
+            ]
+        );
+        assert_eq!(heatmap.max, 3);
+        assert_eq!(heatmap.mean, 2.0);
+        assert_eq!(heatmap.revisited, 4.0 / 6.0);
+        assert_eq!(
+            serde_json::to_string(&heatmap.runs[0]).unwrap(),
+            "[0,0,2,1]"
+        );
+    }
+
+    #[test]
+    fn encodes_empty_heatmap() {
+        let heatmap = Heatmap::from(&HashMap::new());
+
+        assert_eq!(heatmap, Heatmap::default());
+    }
+}
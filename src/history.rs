@@ -0,0 +1,316 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use sqlx::{Postgres, QueryBuilder};
+use std::{fmt, str::FromStr};
+
+use crate::execution::Execution;
+
+// The number of executions on a page, unless the client asks for another one
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+// The largest number of executions on a page
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/*  The columns of an execution in the history. The `heatmap`, the `trace` and the `request`
+    can get large and are left out (they are only read back for a single execution), which
+    is what `#[sqlx(default)]` on their fields of `Execution` is for.
+*/
+pub const COLUMNS: &str = "id, timestamp, commands, result, duration, position_x, position_y, \
+    steps_requested, steps_moved, steps_clamped, min_x, min_y, max_x, max_y, parse_nanos, \
+    simulation_nanos, persistence_nanos, serialization_nanos, request_hash, replay_of";
+
+/*  The columns the history of executions can be sorted by. Each one of them is backed by
+    an index (together with the `id`, which breaks ties and keeps the order stable).
+*/
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Timestamp,
+    Result,
+    Commands,
+    Duration,
+}
+
+impl Sort {
+    fn column(&self) -> &'static str {
+        match self {
+            Sort::Timestamp => "timestamp",
+            Sort::Result => "result",
+            Sort::Commands => "commands",
+            Sort::Duration => "duration",
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/*  The value of the sort column of an execution, as far as a `Cursor` is concerned.
+    Timestamps are kept as microseconds since the Unix epoch, which is the precision the
+    database stores them in.
+*/
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Value {
+    Timestamp(i64),
+    Integer(i32),
+    Float(f64),
+}
+
+/*  A `Cursor` points right behind the last execution of a page, so the next page starts
+    there. Unlike an offset it stays put while new executions come in.
+
+    It's the sort column, its value and the `id` of the execution, e.g. `result:42:1337`.
+    Clients shouldn't care about what's in it though, they just pass it back as it is.
+*/
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Cursor {
+    sort: Sort,
+    value: Value,
+    id: i32,
+}
+
+impl Cursor {
+    // The `Cursor` pointing behind an `Execution`, if it has been stored.
+    pub fn after(execution: &Execution, sort: Sort) -> Option<Cursor> {
+        let value = match sort {
+            Sort::Timestamp => Value::Timestamp(execution.timestamp?.timestamp_micros()),
+            Sort::Result => Value::Integer(execution.result),
+            Sort::Commands => Value::Integer(execution.commands),
+            Sort::Duration => Value::Float(execution.duration?),
+        };
+
+        Some(Cursor {
+            sort,
+            value,
+            id: execution.id?,
+        })
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let column = self.sort.column();
+        match self.value {
+            Value::Timestamp(value) => write!(f, "{}:{}:{}", column, value, self.id),
+            Value::Integer(value) => write!(f, "{}:{}:{}", column, value, self.id),
+            Value::Float(value) => write!(f, "{}:{}:{}", column, value, self.id),
+        }
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid cursor '{}'", text);
+        let parts: Vec<&str> = text.split(':').collect();
+        let (column, value, id) = match parts[..] {
+            [column, value, id] => (column, value, id),
+            _ => return Err(invalid()),
+        };
+
+        let sort = match column {
+            "timestamp" => Sort::Timestamp,
+            "result" => Sort::Result,
+            "commands" => Sort::Commands,
+            "duration" => Sort::Duration,
+            _ => return Err(invalid()),
+        };
+        let value = match sort {
+            Sort::Timestamp => value.parse().map(Value::Timestamp).ok(),
+            Sort::Result | Sort::Commands => value.parse().map(Value::Integer).ok(),
+            Sort::Duration => value.parse().map(Value::Float).ok(),
+        };
+
+        Ok(Cursor {
+            sort,
+            value: value.ok_or_else(invalid)?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/*  A `Filter` is the query parameters of `/executions`: the ranges executions have to be
+    in (all of them inclusive), how they are sorted, how many of them there are on a page
+    (`limit`) and where the page starts (`cursor`).
+
+    `from` and `to` are RFC 3339 timestamps (e.g. `2022-12-24T18:00:00Z`), the duration is
+    in seconds just like in the `Response`.
+*/
+#[derive(Deserialize, Debug, Default)]
+pub struct Filter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub min_result: Option<i32>,
+    pub max_result: Option<i32>,
+    pub min_commands: Option<i32>,
+    pub max_commands: Option<i32>,
+    pub min_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+    #[serde(default)]
+    pub sort: Sort,
+    #[serde(default)]
+    pub order: Order,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+impl Filter {
+    // The number of executions on a page, which is kept between `1` and `MAX_PAGE_SIZE`.
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /*  Builds the query for a page of executions. It fetches one execution more than the
+        `limit`, which tells whether there is another page after this one.
+
+        Executions without a value in the sort column (e.g. the `duration` of one stored
+        before we measured it) can't be placed on any page, so they are left out.
+    */
+    pub fn select(&self) -> Result<QueryBuilder<'static, Postgres>, String> {
+        let mut query = QueryBuilder::new(format!("select {} from executions where ", COLUMNS));
+        let column = self.sort.column();
+        query.push(column).push(" is not null");
+
+        if let Some(from) = self.from {
+            query.push(" and timestamp >= ").push_bind(from);
+        }
+        if let Some(to) = self.to {
+            query.push(" and timestamp <= ").push_bind(to);
+        }
+        if let Some(min) = self.min_result {
+            query.push(" and result >= ").push_bind(min);
+        }
+        if let Some(max) = self.max_result {
+            query.push(" and result <= ").push_bind(max);
+        }
+        if let Some(min) = self.min_commands {
+            query.push(" and commands >= ").push_bind(min);
+        }
+        if let Some(max) = self.max_commands {
+            query.push(" and commands <= ").push_bind(max);
+        }
+        if let Some(min) = self.min_duration {
+            query.push(" and duration >= ").push_bind(min);
+        }
+        if let Some(max) = self.max_duration {
+            query.push(" and duration <= ").push_bind(max);
+        }
+
+        let (direction, comparison) = match self.order {
+            Order::Asc => ("asc", ">"),
+            Order::Desc => ("desc", "<"),
+        };
+
+        if let Some(cursor) = &self.cursor {
+            let cursor: Cursor = cursor.parse()?;
+            if cursor.sort != self.sort {
+                return Err(format!(
+                    "Cursor '{}' doesn't match sort '{}'",
+                    cursor, column
+                ));
+            }
+
+            query.push(format!(" and ({}, id) {} (", column, comparison));
+            match cursor.value {
+                Value::Timestamp(micros) => query.push_bind(
+                    Utc.timestamp_micros(micros)
+                        .single()
+                        .ok_or_else(|| format!("Invalid cursor '{}'", cursor))?,
+                ),
+                Value::Integer(value) => query.push_bind(value),
+                Value::Float(value) => query.push_bind(value),
+            };
+            query.push(", ").push_bind(cursor.id).push(")");
+        }
+
+        query
+            .push(format!(
+                " order by {0} {1}, id {1} limit ",
+                column, direction
+            ))
+            .push_bind(self.limit() + 1);
+
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::extract::{FromRequestParts, Query};
+
+    #[test]
+    fn encodes_cursors() {
+        let execution = Execution {
+            id: Some(1337),
+            timestamp: Utc.timestamp_micros(1417176009000250).single(),
+            commands: 3,
+            result: 42,
+            duration: Some(0.000023),
+            ..Execution::default()
+        };
+
+        for (sort, text) in [
+            (Sort::Timestamp, "timestamp:1417176009000250:1337"),
+            (Sort::Result, "result:42:1337"),
+            (Sort::Commands, "commands:3:1337"),
+            (Sort::Duration, "duration:0.000023:1337"),
+        ] {
+            let cursor = Cursor::after(&execution, sort).unwrap();
+            assert_eq!(cursor.to_string(), text);
+            assert_eq!(text.parse::<Cursor>().unwrap(), cursor);
+        }
+
+        assert!("result:many:1337".parse::<Cursor>().is_err());
+        assert!("color:3:1337".parse::<Cursor>().is_err());
+        assert!("1337".parse::<Cursor>().is_err());
+    }
+
+    #[tokio::test]
+    async fn builds_query_from_filter() {
+        let uri = "/executions?from=2022-12-24T18:00:00Z&min_result=10&max_commands=5\
+            &sort=result&order=asc&limit=20&cursor=result:42:1337";
+        let request = axum::http::Request::builder().uri(uri).body(()).unwrap();
+        let (mut parts, _) = request.into_parts();
+        let Query(filter) = Query::<Filter>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            filter.select().unwrap().sql(),
+            format!(
+                "select {} from executions where result is not null \
+            and timestamp >= $1 and result >= $2 and commands <= $3 \
+            and (result, id) > ($4, $5) order by result asc, id asc limit $6",
+                COLUMNS
+            )
+        );
+
+        let filter = Filter::default();
+        assert_eq!(
+            filter.select().unwrap().sql(),
+            format!(
+                "select {} from executions where timestamp is not null \
+            order by timestamp desc, id desc limit $1",
+                COLUMNS
+            )
+        );
+        assert_eq!(filter.limit(), DEFAULT_PAGE_SIZE);
+
+        let filter = Filter {
+            sort: Sort::Duration,
+            cursor: Some("result:42:1337".to_string()),
+            ..Filter::default()
+        };
+        assert!(filter.select().is_err());
+    }
+}
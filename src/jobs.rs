@@ -0,0 +1,464 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{error::Error as SqlError, types::Json, FromRow, Pool, Postgres};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{sync::Notify, task, time};
+
+use crate::{
+    api::Options,
+    error::Error,
+    execution::Execution,
+    patterns::Plan,
+    timing::{nanos, Timings},
+};
+
+// How often a running job writes down its progress (and finds out whether it was cancelled)
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+// How long a running job may go without writing down its progress before it's taken over
+pub const STALE_AFTER: Duration = Duration::from_secs(10);
+
+// How often an idle worker looks for jobs it hasn't been woken up for
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/*  The states a `Job` goes through: it's `queued` until a worker picks it up, `running`
+    while the robot moves and then either `completed`, `failed` or `cancelled`. The last
+    three are final, nothing happens to a job after that.
+
+    The database keeps the status as plain text (e.g. `running`).
+*/
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl Status {
+    pub fn is_final(&self) -> bool {
+        matches!(self, Status::Completed | Status::Failed | Status::Cancelled)
+    }
+}
+
+/*  A `Job` is a `Request` which is calculated in the background, for requests which take
+    longer than a client is willing to wait for a response. Everything about it lives in
+    the database (not just in memory), so a restart of the app doesn't lose any of them:
+    unfinished jobs are simply started over (see `claim`).
+
+    `progress` is the percentage of the steps the robot has taken so far, `execution_id`
+    the `Execution` a completed job has saved and `error` what made a job fail. `options`
+    are the `Options` of `/path` the job was created with and `parse_nanos` the time it took
+    to parse its request, which is handed on to its `Execution` (see `Timings`).
+*/
+#[derive(FromRow, PartialEq, Debug, Clone)]
+pub struct Job {
+    pub id: i32,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    pub status: Status,
+    pub progress: f64,
+    pub request: Json<Plan>,
+    pub options: Json<Options>,
+    pub execution_id: Option<i32>,
+    pub error: Option<String>,
+    pub parse_nanos: Option<i64>,
+}
+
+impl Job {
+    // Stores a new `Job`, which is `queued` until it's started.
+    pub async fn create(
+        state: Pool<Postgres>,
+        request: Plan,
+        options: Options,
+        parse: Duration,
+    ) -> Result<Job, SqlError> {
+        let job: Job = sqlx::query_as(
+            r#"insert into jobs (request, options, parse_nanos) values ($1, $2, $3) returning *"#,
+        )
+        .bind(Json(request))
+        .bind(Json(options))
+        .bind(nanos(parse))
+        .fetch_one(&state)
+        .await?;
+
+        Ok(job)
+    }
+
+    pub async fn find(state: Pool<Postgres>, id: i32) -> Result<Option<Job>, SqlError> {
+        let job: Option<Job> = sqlx::query_as(r#"select * from jobs where id = $1"#)
+            .bind(id)
+            .fetch_optional(&state)
+            .await?;
+
+        Ok(job)
+    }
+
+    /*  Cancels a `Job` which hasn't finished yet. A running job notices it the next time it
+        writes down its progress and stops right there. Jobs which have already finished
+        stay the way they are, so the caller can tell from the `status` whether cancelling
+        it worked.
+    */
+    pub async fn cancel(state: Pool<Postgres>, id: i32) -> Result<Option<Job>, SqlError> {
+        let job: Option<Job> = sqlx::query_as(
+            r#"update jobs set status = 'cancelled', updated = now()
+            where id = $1 and status in ('queued', 'running') returning *"#,
+        )
+        .bind(id)
+        .fetch_optional(&state)
+        .await?;
+
+        match job {
+            Some(job) => Ok(Some(job)),
+            None => Job::find(state, id).await,
+        }
+    }
+
+    /*  Claims the oldest `Job` nobody is working on and marks it as `running` (from scratch),
+        all in a single statement. `skip locked` makes concurrent workers (of this or any
+        other instance of the app) pass over the rows the others are about to claim, so no
+        job is ever picked up twice.
+
+        Jobs which are `running`, but haven't written down their progress for `STALE_AFTER`,
+        were interrupted by a restart of the app. They are claimed again and start over,
+        since the robot's movements are only stored once they are complete.
+    */
+    async fn claim(state: Pool<Postgres>) -> Result<Option<Job>, SqlError> {
+        let job: Option<Job> = sqlx::query_as(
+            r#"update jobs set status = 'running', progress = 0, updated = now()
+            where id = (
+                select id from jobs
+                where status = 'queued'
+                or (status = 'running' and updated < now() - make_interval(secs => $1))
+                order by id limit 1 for update skip locked
+            ) returning *"#,
+        )
+        .bind(STALE_AFTER.as_secs_f64())
+        .fetch_optional(&state)
+        .await?;
+
+        Ok(job)
+    }
+
+    // Writes down the progress of a running `Job`, returning its status.
+    async fn advance(state: Pool<Postgres>, id: i32, progress: f64) -> Result<Status, SqlError> {
+        let (status,): (Status,) = sqlx::query_as(
+            r#"update jobs set progress = $2, updated = now()
+            where id = $1 and status = 'running' returning status"#,
+        )
+        .bind(id)
+        .bind(progress)
+        .fetch_optional(&state)
+        .await?
+        .unwrap_or((Status::Cancelled,));
+
+        Ok(status)
+    }
+
+    /*  Saves the `Execution` of a `Job` (along with the time that took) and marks it as
+        `completed`, all in the same transaction: a job which has been cancelled at the very
+        last moment doesn't leave an `Execution` behind.
+    */
+    async fn complete(
+        state: Pool<Postgres>,
+        id: i32,
+        execution: Execution,
+    ) -> Result<(), SqlError> {
+        let mut transaction = state.begin().await?;
+        let start_time = Instant::now();
+        let execution = execution.save_with(&mut transaction).await?;
+        if let Some(execution_id) = execution.id {
+            let timings = Timings {
+                persistence: Some(nanos(start_time.elapsed())),
+                ..Timings::default()
+            };
+            Execution::record_timings(&mut transaction, execution_id, timings).await?;
+        }
+
+        let completed = sqlx::query(
+            r#"update jobs set status = 'completed', progress = 100, execution_id = $2,
+            updated = now() where id = $1 and status = 'running'"#,
+        )
+        .bind(id)
+        .bind(execution.id)
+        .execute(&mut transaction)
+        .await?;
+
+        if completed.rows_affected() == 1 {
+            transaction.commit().await
+        } else {
+            transaction.rollback().await
+        }
+    }
+
+    async fn fail(state: Pool<Postgres>, id: i32, error: String) -> Result<(), SqlError> {
+        sqlx::query(
+            r#"update jobs set status = 'failed', error = $2, updated = now()
+            where id = $1 and status = 'running'"#,
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&state)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/*  The workers calculating the jobs in the background. There's a fixed number of them
+    (`MAX_JOBS`, see `Config`), which is how many jobs run at the same time at most, no matter
+    how many are created. Every worker claims one job after the other, until there are none
+    left, and then waits until it's woken up by a new one (or `POLL_INTERVAL` has passed,
+    to pick up jobs created by other instances of the app and ones which were interrupted).
+*/
+#[derive(Clone, Debug)]
+pub struct Workers {
+    wake: Arc<Notify>,
+}
+
+impl Workers {
+    pub fn start(state: Pool<Postgres>, count: usize) -> Self {
+        let wake = Arc::new(Notify::new());
+        for _ in 0..count {
+            tokio::spawn(work(state.clone(), wake.clone()));
+        }
+
+        Workers { wake }
+    }
+
+    // Tells one of the idle workers (if any) that there's a new job waiting.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+async fn work(state: Pool<Postgres>, wake: Arc<Notify>) {
+    loop {
+        match Job::claim(state.clone()).await {
+            Ok(Some(job)) => {
+                execute(state.clone(), job).await;
+                continue;
+            }
+            Ok(None) => {}
+            Err(error) => tracing::error!("Unable to claim a job: {:?}", error),
+        }
+
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+// Runs a claimed `Job` to its end, marking it as `failed` if anything goes wrong.
+async fn execute(state: Pool<Postgres>, job: Job) {
+    let id = job.id;
+    if let Err(error) = run(state.clone(), job).await {
+        tracing::error!(job = id, "Job failed: {:?}", error);
+        if let Err(error) = Job::fail(state, id, error.to_string()).await {
+            tracing::error!(job = id, "Unable to record failure of job: {:?}", error);
+        }
+    }
+}
+
+/*  The simulation runs on a thread of its own, while this task writes its progress to the
+    database every `PROGRESS_INTERVAL` (and stops it once the job has been cancelled, or its
+    progress can't be written down anymore).
+*/
+async fn run(state: Pool<Postgres>, job: Job) -> Result<(), Error> {
+    let id = job.id;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let progress = Arc::new(Mutex::new(0.0));
+
+    let mut simulation = {
+        let cancelled = cancelled.clone();
+        let progress = progress.clone();
+        task::spawn_blocking(move || {
+            Execution::default().calculate(job.request.0, |current| {
+                if let Ok(mut progress) = progress.lock() {
+                    *progress = current.percent;
+                }
+                !cancelled.load(Ordering::Relaxed)
+            })
+        })
+    };
+
+    let mut interval = time::interval(PROGRESS_INTERVAL);
+    let outcome = loop {
+        tokio::select! {
+            outcome = &mut simulation => break outcome
+                .map_err(|e| Error::Internal(format!("Unable to calculate job: {}", e)))?,
+            _ = interval.tick() => {
+                let current = progress.lock().map(|progress| *progress).unwrap_or_default();
+                match Job::advance(state.clone(), id, current).await {
+                    Ok(Status::Cancelled) => cancelled.store(true, Ordering::Relaxed),
+                    Ok(_) => {}
+                    Err(error) => {
+                        cancelled.store(true, Ordering::Relaxed);
+                        return Err(error.into());
+                    }
+                }
+            }
+        }
+    };
+
+    if let Some((mut execution, simulation)) = outcome {
+        execution.parse_nanos = job.parse_nanos;
+        job.options.record(&mut execution, &simulation);
+        Job::complete(state, id, execution).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{handle_cancel_job, Request},
+        clock::Clock,
+        error::Path,
+        format::Format,
+        types::{Command, Direction, Position},
+    };
+    use axum::extract::State;
+
+    // A `Request` taking `count` commands of `steps` steps back and forth.
+    fn request(count: usize, steps: i32) -> Request {
+        Request {
+            start: Position { x: 0, y: 0 },
+            commands: (0..count)
+                .map(|index| Command {
+                    direction: if index % 2 == 0 {
+                        Direction::East
+                    } else {
+                        Direction::West
+                    },
+                    steps,
+                })
+                .collect(),
+        }
+    }
+
+    async fn queue(pool: &Pool<Postgres>, request: Request) -> Job {
+        Job::create(
+            pool.clone(),
+            Plan::from(request),
+            Options::default(),
+            Duration::ZERO,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn tells_final_states() {
+        assert!(!Status::Queued.is_final());
+        assert!(!Status::Running.is_final());
+        assert!(Status::Completed.is_final());
+        assert!(Status::Failed.is_final());
+        assert!(Status::Cancelled.is_final());
+
+        assert_eq!(
+            serde_json::to_string(&Status::Cancelled).unwrap(),
+            "\"cancelled\""
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn claims_every_job_once(pool: Pool<Postgres>) {
+        let first = queue(&pool, request(1, 1)).await;
+        let second = queue(&pool, request(1, 1)).await;
+
+        let claimed = Job::claim(pool.clone()).await.unwrap().unwrap();
+        assert_eq!((claimed.id, claimed.status), (first.id, Status::Running));
+        assert_eq!(
+            Job::claim(pool.clone()).await.unwrap().unwrap().id,
+            second.id
+        );
+        assert_eq!(Job::claim(pool.clone()).await.unwrap(), None);
+
+        // A running job which hasn't made any progress in a while is taken over
+        sqlx::query("update jobs set updated = now() - interval '1 minute' where id = $1")
+            .bind(first.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            Job::claim(pool.clone()).await.unwrap().unwrap().id,
+            first.id
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn completes_jobs_with_their_execution(pool: Pool<Postgres>) {
+        let job = queue(&pool, request(2, 3)).await;
+        let claimed = Job::claim(pool.clone()).await.unwrap().unwrap();
+        run(pool.clone(), claimed).await.unwrap();
+
+        let job = Job::find(pool.clone(), job.id).await.unwrap().unwrap();
+        assert_eq!((job.status, job.progress), (Status::Completed, 100.0));
+
+        let execution = Execution::find(pool.clone(), job.execution_id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((execution.commands, execution.result), (2, 4));
+        assert!(execution.persistence_nanos.is_some());
+        assert_eq!(execution.serialization_nanos, None);
+
+        // Finished jobs can't be cancelled anymore
+        let cancelled = handle_cancel_job(
+            State(pool.clone()),
+            Path(job.id),
+            Clock::default(),
+            Format::Json,
+        )
+        .await;
+        assert!(matches!(cancelled, Err(Error::Unprocessable(_))));
+        let job = Job::find(pool, job.id).await.unwrap().unwrap();
+        assert_eq!(job.status, Status::Completed);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn stops_cancelled_jobs(pool: Pool<Postgres>) {
+        let job = queue(&pool, request(10000, 99999)).await;
+        let claimed = Job::claim(pool.clone()).await.unwrap().unwrap();
+
+        let cancelled = Job::cancel(pool.clone(), job.id).await.unwrap().unwrap();
+        assert_eq!(cancelled.status, Status::Cancelled);
+        run(pool.clone(), claimed).await.unwrap();
+
+        let job = Job::find(pool.clone(), job.id).await.unwrap().unwrap();
+        assert_eq!((job.status, job.execution_id), (Status::Cancelled, None));
+
+        let (executions,): (i64,) = sqlx::query_as("select count(*) from executions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(executions, 0);
+
+        // Cancelling it again is fine, and claiming it isn't possible anymore
+        let again = handle_cancel_job(
+            State(pool.clone()),
+            Path(job.id),
+            Clock::default(),
+            Format::Json,
+        )
+        .await;
+        assert!(again.is_ok());
+        assert_eq!(Job::claim(pool).await.unwrap(), None);
+    }
+}
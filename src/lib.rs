@@ -1,12 +1,44 @@
 mod api;
+mod clock;
+mod config;
+mod error;
 mod execution;
+mod format;
+mod heatmap;
+mod history;
+mod jobs;
+mod patterns;
+mod render;
+mod session;
+mod timing;
+mod trace;
 mod types;
+mod validation;
 
-use axum::{routing::post, Router};
+use axum::{
+    extract::FromRef,
+    middleware,
+    routing::{get, post},
+    Router,
+};
 use shuttle_service::ShuttleAxum;
 use sqlx::PgPool;
 use sync_wrapper::SyncWrapper;
 
+use config::Config;
+use jobs::Workers;
+
+/*  Everything the handlers share: the database connection pool, the `Config` and the
+    `Workers` calculating jobs. Handlers take whichever part of it they need (e.g.
+    `State<Pool<Postgres>>`), that's what `FromRef` is for.
+*/
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub config: Config,
+    pub workers: Workers,
+}
+
 /*  The entire `main` function is `async` meaning it's safe to spawn as many of the processes
     inside of it as required, i.e. to scale the API appropriately.
 */
@@ -21,15 +53,53 @@ async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> ShuttleAxum {
         .await
         .expect("Unable to run migrations");
 
+    /*  The workers calculating jobs in the background are started right away, so jobs which
+        were queued (or interrupted) when the app stopped don't get lost (see `jobs.rs`).
+    */
+    let config = Config::from_env();
+    let workers = Workers::start(pool.clone(), config.max_jobs);
+
     /*  This is the main router object where we're mounting the routes into. The challenge
         only stipulates a single route, for which we are passing a single "handler" or controller.
+        The other routes give access to the data we have stored along the way.
 
         We are also passing along the database connection pool as "state" to ensure we can use
-        it to store our execution results later.
+        it to store our execution results later. The middlewares give every request a
+        correlation id and a timeout (see `error.rs`).
     */
     let app = Router::new()
         .route("/path", post(api::handle_enter_path))
-        .with_state(pool);
+        .route("/path/stream", post(api::handle_stream))
+        .route("/session", get(api::handle_session))
+        .route("/path/batch", post(api::handle_batch))
+        .route("/path/validate", post(api::handle_validate))
+        .route("/path/png", post(api::handle_render_png))
+        .route("/path/svg", post(api::handle_render_svg))
+        .route("/path/ascii", post(api::handle_render_ascii))
+        .route("/path/geojson", post(api::handle_render_geojson))
+        .route("/jobs", post(api::handle_create_job))
+        .route(
+            "/jobs/:id",
+            get(api::handle_job).delete(api::handle_cancel_job),
+        )
+        .route("/executions", get(api::handle_executions))
+        .route("/executions/:id", get(api::handle_execution))
+        .route("/executions/:id/replay", post(api::handle_replay))
+        .route("/executions/:id/heatmap", get(api::handle_heatmap))
+        .route("/executions/:id/png", get(api::handle_execution_png))
+        .route("/executions/:id/svg", get(api::handle_execution_svg))
+        .route("/executions/:id/ascii", get(api::handle_execution_ascii))
+        .route(
+            "/executions/:id/geojson",
+            get(api::handle_execution_geojson),
+        )
+        .layer(middleware::from_fn(error::time_out))
+        .layer(middleware::from_fn(error::correlate))
+        .with_state(AppState {
+            pool,
+            config,
+            workers,
+        });
 
     let wrapper = SyncWrapper::new(app);
 
@@ -0,0 +1,606 @@
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api::Request,
+    types::{Command, Direction, Position, Region, FIELD_LIMIT},
+};
+
+/*  The largest budget of `steps` a `random_bounce` may be given. The robot doesn't know
+    where it's going, so the budget is the only thing keeping it from bouncing around (and
+    the simulation from running) forever. A million steps cross the grid five times over.
+*/
+pub const MAX_BOUNCE_STEPS: i32 = 1000000;
+
+/*  The largest `radius` of a `spot`. The spiral covers every field of a square twice as
+    wide as the `radius`, so the steps it takes grow with its square: a `radius` of 1000
+    takes about four million steps.
+*/
+pub const MAX_SPOT_RADIUS: i32 = 1000;
+
+/*  Besides plain lane sweeps our robots know a few cleaning modes which are more
+    convenient to describe as a single instruction rather than a long list of commands.
+    A `Pattern` is exactly that: a high-level mode which gets expanded into the `Command`s
+    the robot would execute for it.
+
+    `serde` represents each variant as an object with a single key, e.g.
+    `{"spot": {"center": {"x": 0, "y": 0}, "radius": 2}}`, which can't be mistaken for
+    a regular `Command`.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    Spot { center: Position, radius: i32 },
+    Edge { region: Region },
+    RandomBounce { seed: u64, steps: i32 },
+}
+
+/*  An `Instruction` is a single item of the `commands` array of a request. It's either
+    a `Command` or a `Pattern`, which is why it's serialized `untagged`.
+*/
+#[derive(Serialize, PartialEq, Eq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Instruction {
+    Command(Command),
+    Pattern(Pattern),
+}
+
+/*  Deserializing an `untagged` enum tries each variant in turn and, if none of them fits,
+    only tells that nothing did, whatever was wrong with the item. Instead we pick the
+    variant by its keys: an item with a `direction` is a `Command`, anything else has to be
+    a `Pattern`. That way the client gets to see what `serde` has to say about it (e.g. an
+    unknown variant or a missing field), just like for any other part of the request.
+
+    The item is read into a `Value` first, which works the same for every `Syntax`.
+*/
+impl<'de> Deserialize<'de> for Instruction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let is_command = value
+            .as_object()
+            .is_some_and(|item| item.contains_key("direction"));
+
+        if is_command {
+            Command::deserialize(value).map(Instruction::Command)
+        } else {
+            Pattern::deserialize(value).map(Instruction::Pattern)
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+impl Instruction {
+    /*  The `Command`s the `Instruction` stands for when the robot is at `position`, along
+        with the `Position` it ends up at afterwards.
+    */
+    pub fn expand(&self, position: Position) -> (Vec<Command>, Position) {
+        match self {
+            Instruction::Command(command) => (
+                vec![command.clone()],
+                position.travel(&command.direction, command.steps),
+            ),
+            Instruction::Pattern(pattern) => pattern.expand(position),
+        }
+    }
+}
+
+/*  A `Plan` is the way a request looks like "on the wire", with `Pattern`s mixed in between
+    the `Command`s. It's what gets validated (see `validation::validate`), so problems are
+    pointed out in what the client has actually sent, and what gets stored along with an
+    `Execution` (see `Execution::calculate`). Deserializing a `Request` goes through it as
+    well (see the `serde` attribute on `Request` in `api.rs`); the rest of the app only
+    deals with plain commands.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Plan {
+    pub start: Position,
+    pub commands: Vec<Instruction>,
+}
+
+/*  Expanding a `Pattern` needs to know where the robot is at that moment, so we follow
+    it through the commands of the `Plan` using `Position::travel`, which ends up exactly
+    where the simulation in `Execution::calculate` would.
+*/
+impl From<Plan> for Request {
+    fn from(plan: Plan) -> Self {
+        let mut position = plan.start;
+        let mut commands = Vec::with_capacity(plan.commands.len());
+
+        for instruction in &plan.commands {
+            let (expanded, destination) = instruction.expand(position);
+            position = destination;
+            commands.extend(expanded);
+        }
+
+        Request {
+            start: plan.start,
+            commands,
+        }
+    }
+}
+
+// A `Request` is a `Plan` without any `Pattern`s.
+impl From<Request> for Plan {
+    fn from(request: Request) -> Self {
+        Plan {
+            start: request.start,
+            commands: request
+                .commands
+                .into_iter()
+                .map(Instruction::Command)
+                .collect(),
+        }
+    }
+}
+
+impl Pattern {
+    /*  Turns the `Pattern` into the `Command`s required to execute it from `position`,
+        also returning the `Position` the robot ends up at afterwards.
+    */
+    pub fn expand(&self, position: Position) -> (Vec<Command>, Position) {
+        match self {
+            Pattern::Spot { center, radius } => spot(position, *center, *radius),
+            Pattern::Edge { region } => edge(position, *region),
+            Pattern::RandomBounce { seed, steps } => random_bounce(position, *seed, *steps),
+        }
+    }
+
+    /*  The number of steps the `Pattern` takes once the robot has got to where it starts,
+        worked out without expanding it (see `validation::validate`). A `random_bounce` may
+        take fewer, if the robot is stuck outside of the grid.
+    */
+    pub fn cost(&self) -> i64 {
+        match self {
+            Pattern::Spot { center, radius } => {
+                let side = 2 * shrunk(center.clamped(), *radius) as i64 + 1;
+                side * side - 1
+            }
+            Pattern::Edge { region } => {
+                let region = region.normalized();
+                2 * (region.max.x as i64 - region.min.x as i64)
+                    + 2 * (region.max.y as i64 - region.min.y as i64)
+            }
+            Pattern::RandomBounce { steps, .. } => (*steps).max(0) as i64,
+        }
+    }
+}
+
+/*  The spot-clean mode drives to the `center` and then cleans a square around it in a
+    spiral, moving outwards one lane at a time until it has covered every field within
+    `radius` of the `center` (at most `MAX_SPOT_RADIUS`, see `validation::validate`). The
+    spiral is shrunk if necessary so it never touches the edge of the grid (as set by
+    `FIELD_LIMIT`).
+*/
+fn spot(position: Position, center: Position, radius: i32) -> (Vec<Command>, Position) {
+    let center = center.clamped();
+    let radius = shrunk(center, radius);
+
+    let (mut commands, mut position) = approach(position, center);
+
+    let directions = [
+        Direction::East,
+        Direction::North,
+        Direction::West,
+        Direction::South,
+    ];
+    let mut turn = directions.iter().cycle();
+
+    /*  Each lane of the spiral is as long as the previous one, every other lane one
+        field longer. The final lane closes the square along its outer edge.
+    */
+    for length in 1..=(radius * 2) {
+        for _ in 0..2 {
+            let direction = turn.next().unwrap();
+            position = position.travel(direction, length);
+            commands.push(Command {
+                direction: direction.clone(),
+                steps: length,
+            });
+        }
+    }
+
+    if radius > 0 {
+        let direction = turn.next().unwrap();
+        position = position.travel(direction, radius * 2);
+        commands.push(Command {
+            direction: direction.clone(),
+            steps: radius * 2,
+        });
+    }
+
+    (commands, position)
+}
+
+// The `radius` of a spiral around `center` which keeps it on the grid.
+fn shrunk(center: Position, radius: i32) -> i32 {
+    radius
+        .max(0)
+        .min(FIELD_LIMIT - center.x.abs())
+        .min(FIELD_LIMIT - center.y.abs())
+}
+
+/*  The edge-clean mode drives to the lower left corner of the `region` and follows its
+    boundary counter-clockwise until it's back where it started from. Whatever part of the
+    `region` is outside of the grid is ignored.
+*/
+fn edge(position: Position, region: Region) -> (Vec<Command>, Position) {
+    let region = region.normalized();
+    let width = region.max.x - region.min.x;
+    let height = region.max.y - region.min.y;
+
+    let (mut commands, position) = approach(position, region.min);
+
+    for (direction, steps) in [
+        (Direction::East, width),
+        (Direction::North, height),
+        (Direction::West, width),
+        (Direction::South, height),
+    ] {
+        if steps > 0 {
+            commands.push(Command { direction, steps });
+        }
+    }
+
+    (commands, position)
+}
+
+/*  Low-end robots don't plan anything: they drive straight ahead until they bump into
+    something and then turn into a random other direction. The only things a robot can
+    bump into are the edges of the grid (the same ones `Position::shift` stops at), and
+    it keeps going until it has used up its budget of `steps` (at most `MAX_BOUNCE_STEPS`,
+    see `validation::validate`).
+
+    The random numbers come from a `Seed`, so the same `seed` always yields the same path,
+    which makes it possible to compare its coverage against planned paths.
+*/
+fn random_bounce(position: Position, seed: u64, steps: i32) -> (Vec<Command>, Position) {
+    let mut seed = Seed(seed);
+    let mut commands = vec![];
+    let mut position = position;
+    let mut budget = steps.max(0);
+    let mut blocked: Option<Direction> = None;
+
+    while budget > 0 {
+        let directions: Vec<Direction> = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .into_iter()
+        .filter(|d| Some(d) != blocked.as_ref() && position.shift(d) != position)
+        .collect();
+
+        // A robot too far outside of the grid to step back onto it can't go anywhere
+        if directions.is_empty() {
+            break;
+        }
+
+        let direction = directions[seed.next() as usize % directions.len()].clone();
+        let steps = budget.min(distance_to_edge(position, &direction));
+
+        position = position.travel(&direction, steps);
+        budget -= steps;
+        commands.push(Command {
+            direction: direction.clone(),
+            steps,
+        });
+        blocked = Some(direction);
+    }
+
+    (commands, position)
+}
+
+// How many steps the robot can take into a `Direction` before it hits the edge of the grid.
+fn distance_to_edge(position: Position, direction: &Direction) -> i32 {
+    match direction {
+        Direction::North => FIELD_LIMIT - position.y,
+        Direction::East => FIELD_LIMIT - position.x,
+        Direction::South => FIELD_LIMIT + position.y,
+        Direction::West => FIELD_LIMIT + position.x,
+    }
+}
+
+/*  A tiny pseudo-random number generator (SplitMix64). It's not suitable for anything
+    security related, but it's fast, has no dependencies and, most importantly, yields
+    the same sequence of numbers for the same seed on every platform and in every version
+    of the app.
+*/
+struct Seed(u64);
+
+impl Seed {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/*  The robot can only move along one axis at a time, so getting from `position` to
+    `destination` takes (at most) one horizontal and one vertical `Command`. The distance is
+    taken as `i64`, since a `position` far outside of the grid can be further away from the
+    `destination` than an `i32` reaches (such a robot can't move anyway, see `travel`).
+*/
+fn approach(position: Position, destination: Position) -> (Vec<Command>, Position) {
+    let mut commands = vec![];
+    let steps = |distance: i64| distance.unsigned_abs().min(i32::MAX as u64) as i32;
+
+    let dx = destination.x as i64 - position.x as i64;
+    if dx != 0 {
+        let direction = if dx > 0 {
+            Direction::East
+        } else {
+            Direction::West
+        };
+        commands.push(Command {
+            direction,
+            steps: steps(dx),
+        });
+    }
+
+    let dy = destination.y as i64 - position.y as i64;
+    if dy != 0 {
+        let direction = if dy > 0 {
+            Direction::North
+        } else {
+            Direction::South
+        };
+        commands.push(Command {
+            direction,
+            steps: steps(dy),
+        });
+    }
+
+    let position = commands.iter().fold(position, |position, command| {
+        position.travel(&command.direction, command.steps)
+    });
+
+    (commands, position)
+}
+
+/*  These tests make sure the generated commands actually cover the fields they are
+    supposed to, by running them through the same `Execution` the API uses.
+*/
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::execution::Execution;
+
+    #[tokio::test]
+    async fn spirals_around_spot() {
+        let center = Position { x: 3, y: -4 };
+        let (commands, destination) = Pattern::Spot { center, radius: 2 }.expand(center);
+
+        let execution = Execution::default();
+        let (execution, _) = execution
+            .calculate(
+                Request {
+                    start: center,
+                    commands,
+                },
+                |_| true,
+            )
+            .unwrap_or_default();
+
+        // A 5x5 square, except for the center the robot started from
+        assert_eq!(execution.result, 24);
+        assert_eq!(destination, Position { x: 5, y: -6 });
+    }
+
+    #[test]
+    fn approaches_spot_center_first() {
+        let (commands, _) = Pattern::Spot {
+            center: Position { x: -2, y: 5 },
+            radius: 0,
+        }
+        .expand(Position { x: 0, y: 0 });
+
+        assert_eq!(
+            commands,
+            vec![
+                Command {
+                    direction: Direction::West,
+                    steps: 2
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 5
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn approaches_from_far_outside_of_grid() {
+        let start = Position {
+            x: i32::MIN,
+            y: i32::MAX,
+        };
+        let (commands, destination) = Pattern::Spot {
+            center: Position { x: 0, y: 0 },
+            radius: 0,
+        }
+        .expand(start);
+
+        assert_eq!(
+            commands,
+            vec![
+                Command {
+                    direction: Direction::East,
+                    steps: i32::MAX
+                },
+                Command {
+                    direction: Direction::South,
+                    steps: i32::MAX
+                }
+            ]
+        );
+        // Too far away to step onto the grid
+        assert_eq!(destination, start);
+    }
+
+    #[test]
+    fn shrinks_spot_at_field_limit() {
+        let center = Position {
+            x: FIELD_LIMIT + 10,
+            y: 0,
+        };
+        let (commands, _) = Pattern::Spot { center, radius: 5 }.expand(Position { x: 0, y: 0 });
+
+        // Driving to the edge of the grid is all that's left
+        assert_eq!(
+            commands,
+            vec![Command {
+                direction: Direction::East,
+                steps: FIELD_LIMIT
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_region_edge() {
+        let region = Region {
+            min: Position { x: 0, y: 0 },
+            max: Position { x: 4, y: 2 },
+        };
+        let start = Position { x: -3, y: 1 };
+        let (commands, destination) = Pattern::Edge { region }.expand(start);
+
+        let execution = Execution::default();
+        let execution = execution
+            .calculate(Request { start, commands }, |_| true)
+            .unwrap_or_default()
+            .0;
+
+        // 2 fields on the way to the corner which aren't part of the 12 edge fields
+        assert_eq!(execution.result, 2 + 12);
+        assert_eq!(destination, region.min);
+    }
+
+    #[tokio::test]
+    async fn bounces_reproducibly() {
+        let start = Position {
+            x: FIELD_LIMIT - 3,
+            y: FIELD_LIMIT - 2,
+        };
+        let pattern = Pattern::RandomBounce {
+            seed: 42,
+            steps: 250000,
+        };
+        let (commands, destination) = pattern.expand(start);
+
+        assert_eq!((commands.clone(), destination), pattern.expand(start));
+        assert_eq!(
+            commands.iter().map(|c| c.steps).sum::<i32>(),
+            250000,
+            "the whole budget should be used up"
+        );
+        assert!(commands.len() > 1, "the robot should have bounced");
+        assert!(!destination.out_of_bounds());
+
+        let execution = Execution::default();
+        let execution = execution
+            .calculate(Request { start, commands }, |_| true)
+            .unwrap_or_default()
+            .0;
+
+        assert!(execution.result > 0 && execution.result <= 250000);
+
+        let (commands, _) = Pattern::RandomBounce { seed: 7, steps: 10 }.expand(Position {
+            x: FIELD_LIMIT + 2,
+            y: 0,
+        });
+        assert!(commands.is_empty());
+    }
+
+    // Starting where the pattern does, the robot takes exactly the steps of its `cost`.
+    #[test]
+    fn costs_what_it_expands_to() {
+        let steps = |pattern: Pattern, start| {
+            let (commands, _) = pattern.expand(start);
+            commands.iter().map(|c| c.steps as i64).sum::<i64>()
+        };
+
+        let center = Position {
+            x: FIELD_LIMIT - 2,
+            y: 0,
+        };
+        let spot = Pattern::Spot { center, radius: 5 };
+        assert_eq!(spot.cost(), 24);
+        assert_eq!(steps(spot.clone(), center), spot.cost());
+
+        let region = Region {
+            min: Position { x: 0, y: 0 },
+            max: Position {
+                x: 4,
+                y: FIELD_LIMIT + 5,
+            },
+        };
+        let edge = Pattern::Edge { region };
+        assert_eq!(steps(edge.clone(), region.min), edge.cost());
+
+        let bounce = Pattern::RandomBounce {
+            seed: 3,
+            steps: 500,
+        };
+        assert_eq!(steps(bounce.clone(), region.min), bounce.cost());
+    }
+
+    #[test]
+    fn parses_inline_patterns() {
+        let request: Request = serde_json::from_str(
+            r#"{
+                "start": {"x": 0, "y": 0},
+                "commands": [
+                    {"direction": "east", "steps": 2},
+                    {"spot": {"center": {"x": 2, "y": 0}, "radius": 1}},
+                    {"edge": {"region": {"min": {"x": 5, "y": 5}, "max": {"x": 5, "y": 6}}}}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(request.commands.len(), 1 + 5 + 2 + 2);
+        assert_eq!(
+            request.commands[1],
+            Command {
+                direction: Direction::East,
+                steps: 1
+            }
+        );
+        assert_eq!(
+            request.commands[6],
+            Command {
+                direction: Direction::East,
+                steps: 2
+            }
+        );
+    }
+
+    #[test]
+    fn tells_what_is_wrong_with_an_instruction() {
+        use crate::format::Syntax;
+
+        let json = br#"{
+            "start": {"x": 0, "y": 0},
+            "commands": [
+                {"direction": "east", "steps": 2},
+                {"direction": "up", "steps": 2}
+            ]
+        }"#;
+        let (message, line) = Syntax::Json.parse::<Plan>(json).unwrap_err();
+        assert!(message.starts_with("unknown variant `up`"), "{}", message);
+        // The item is read as a whole first, so the line is the one right after it
+        assert_eq!(line, Some(6));
+
+        let yaml = b"start: {x: 0, y: 0}\ncommands:\n  - direction: east\n    steps: 2\n  - direction: east\n";
+        let (message, _) = Syntax::Yaml.parse::<Plan>(yaml).unwrap_err();
+        assert!(message.contains("missing field `steps`"), "{}", message);
+
+        let toml = b"start = { x = 0, y = 0 }\n\n[[commands]]\n[commands.sweep]\nradius = 2\n";
+        let (message, _) = Syntax::Toml.parse::<Plan>(toml).unwrap_err();
+        assert!(message.contains("unknown variant `sweep`"), "{}", message);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+
+use super::{extent, is_obstacle, path};
+use crate::{execution::Simulation, types::Position};
+
+// The largest number of columns and rows of a map, anything bigger wouldn't be readable
+pub const MAX_SIZE: i64 = 200;
+
+/*  Renders the `Simulation` of a robot which started at `start` as a plain text map of the
+    grid around its path, one line per row (north at the top):
+
+    - `S` is where the robot started and `E` where it ended up (`E` wins if they're the same)
+    - `!` is a vertex where the robot dropped steps at the edge of the grid
+    - `#` is a vertex the robot has cleaned
+    - `X` is an obstacle, i.e. beyond the edge of the grid
+    - `.` is a vertex the robot hasn't touched
+
+    This is meant for small paths only (e.g. in the tests, to make failures readable), so
+    there is no map for paths spanning more than `MAX_SIZE` columns or rows.
+*/
+pub fn render(start: Position, simulation: &Simulation) -> Option<String> {
+    let path = path(start, simulation);
+    let region = extent(&path);
+    let end = *path.last().unwrap();
+
+    let columns = region.max.x as i64 - region.min.x as i64 + 1;
+    let rows = region.max.y as i64 - region.min.y as i64 + 1;
+    if columns > MAX_SIZE || rows > MAX_SIZE {
+        return None;
+    }
+
+    let clamped: HashSet<Position> = simulation
+        .details
+        .iter()
+        .filter(|detail| detail.clamped > 0)
+        .map(|detail| detail.end)
+        .collect();
+
+    let mut map = String::with_capacity(((columns + 1) * rows) as usize);
+    for y in (region.min.y..=region.max.y).rev() {
+        for x in region.min.x..=region.max.x {
+            let position = Position { x, y };
+            let symbol = if position == end {
+                'E'
+            } else if position == start {
+                'S'
+            } else if clamped.contains(&position) {
+                '!'
+            } else if simulation.visits.contains_key(&position) {
+                '#'
+            } else if is_obstacle(x as i64, y as i64) {
+                'X'
+            } else {
+                '.'
+            };
+            map.push(symbol);
+        }
+        map.push('\n');
+    }
+
+    Some(map)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::Request,
+        execution::Execution,
+        types::{Command, Direction, FIELD_LIMIT},
+    };
+
+    #[tokio::test]
+    async fn renders_map() {
+        let start = Position { x: 0, y: 0 };
+        let request = Request {
+            start,
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 3,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 2,
+                },
+                Command {
+                    direction: Direction::West,
+                    steps: 1,
+                },
+            ],
+        };
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+
+        assert_eq!(
+            render(start, &simulation).unwrap(),
+            [
+                "......", //
+                "...E#.", //
+                "....#.", //
+                ".S###.", //
+                "......", //
+            ]
+            .map(|row| format!("{}\n", row))
+            .concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn renders_edge_of_grid() {
+        let start = Position {
+            x: FIELD_LIMIT - 1,
+            y: 0,
+        };
+        let request = Request {
+            start,
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 3,
+                },
+                Command {
+                    direction: Direction::South,
+                    steps: 1,
+                },
+            ],
+        };
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+
+        assert_eq!(
+            render(start, &simulation).unwrap(),
+            [
+                "...X", //
+                ".S!X", //
+                "..EX", //
+                "...X", //
+            ]
+            .map(|row| format!("{}\n", row))
+            .concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_large_maps() {
+        let start = Position { x: 0, y: 0 };
+        let request = Request {
+            start,
+            commands: vec![Command {
+                direction: Direction::North,
+                steps: 500,
+            }],
+        };
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+
+        assert_eq!(render(start, &simulation), None);
+    }
+}
@@ -0,0 +1,224 @@
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::{json, Value};
+
+use super::path;
+use crate::{
+    execution::{Execution, Simulation},
+    heatmap::{runs, Run},
+    types::Position,
+};
+
+/*  Our facility maps don't know about the grid, they use metres. The `Origin` tells where
+    the vertex `0`/`0` of the grid is located on such a map (`x` and `y`, in metres) and
+    how far apart the vertices are (`cell_size`, in metres as well). By default the grid
+    is simply taken as metres.
+
+    It's passed along as query parameters, where `origin_x` and `origin_y` are a bit more
+    telling than just `x` and `y`. A `cell_size` which isn't a positive number would squash
+    the whole path into a single point (or turn it upside down), so it's rejected.
+*/
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct Origin {
+    #[serde(default, rename = "origin_x")]
+    pub x: f64,
+    #[serde(default, rename = "origin_y")]
+    pub y: f64,
+    #[serde(default = "default_cell_size", deserialize_with = "cell_size")]
+    pub cell_size: f64,
+}
+
+fn default_cell_size() -> f64 {
+    1.0
+}
+
+fn cell_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let cell_size = f64::deserialize(deserializer)?;
+    if !(cell_size.is_finite() && cell_size > 0.0) {
+        return Err(D::Error::custom(format!(
+            "cell_size must be a positive number, not {}",
+            cell_size
+        )));
+    }
+
+    Ok(cell_size)
+}
+
+impl Default for Origin {
+    fn default() -> Self {
+        Origin {
+            x: 0.0,
+            y: 0.0,
+            cell_size: default_cell_size(),
+        }
+    }
+}
+
+impl Origin {
+    // Converts a point of the grid (which doesn't have to be a vertex) into metres.
+    fn convert(&self, x: f64, y: f64) -> [f64; 2] {
+        [self.x + x * self.cell_size, self.y + y * self.cell_size]
+    }
+}
+
+/*  Renders the `Simulation` of a robot which started at `start` as a GeoJSON
+    `FeatureCollection` with two features: the path of the robot as a `LineString` and the
+    vertices it has cleaned as a `MultiPolygon`. Each vertex is a square of `cell_size`
+    around its center, and vertices next to each other in a row are combined into a
+    single rectangle (see `heatmap::runs`) to keep the output small.
+
+    Both features carry the metadata of the `Execution` as their properties, along with
+    a `feature` property telling them apart (see `properties`).
+*/
+pub fn render(
+    start: Position,
+    simulation: &Simulation,
+    execution: &Execution,
+    origin: Origin,
+) -> Value {
+    let line: Vec<[f64; 2]> = path(start, simulation)
+        .iter()
+        .map(|position| origin.convert(position.x as f64, position.y as f64))
+        .collect();
+
+    let polygons: Vec<[[[f64; 2]; 5]; 1]> = runs(&simulation.visits, |_| 1)
+        .into_iter()
+        .map(|Run(x, y, length, _)| {
+            let (left, right) = (x as f64 - 0.5, x as f64 + length as f64 - 0.5);
+            let (bottom, top) = (y as f64 - 0.5, y as f64 + 0.5);
+            [[
+                origin.convert(left, bottom),
+                origin.convert(right, bottom),
+                origin.convert(right, top),
+                origin.convert(left, top),
+                origin.convert(left, bottom),
+            ]]
+        })
+        .collect();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "geometry": { "type": "LineString", "coordinates": line },
+                "properties": properties(execution, "path"),
+            },
+            {
+                "type": "Feature",
+                "geometry": { "type": "MultiPolygon", "coordinates": polygons },
+                "properties": properties(execution, "cleaned"),
+            },
+        ],
+    })
+}
+
+/*  The properties of a feature: the `Execution` with its final position, steps, bounds
+    and timings, flat as most GIS tools like them. Its `heatmap` and `trace` are left out,
+    they would only repeat the geometries (many times over).
+*/
+fn properties(execution: &Execution, feature: &str) -> Value {
+    let mut properties = json!(execution);
+    if let Some(properties) = properties.as_object_mut() {
+        properties.remove("heatmap");
+        properties.remove("trace");
+    }
+    properties["feature"] = json!(feature);
+    properties
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::Request,
+        types::{Command, Direction},
+    };
+
+    #[tokio::test]
+    async fn renders_features_in_metres() {
+        let start = Position { x: 0, y: 0 };
+        let request = Request {
+            start,
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 2,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 1,
+                },
+            ],
+        };
+
+        let (execution, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let origin = Origin {
+            x: 100.0,
+            y: 50.0,
+            cell_size: 0.5,
+        };
+        let geojson = render(start, &simulation, &execution, origin);
+
+        let path = &geojson["features"][0];
+        assert_eq!(
+            path["geometry"],
+            json!({
+                "type": "LineString",
+                "coordinates": [[100.0, 50.0], [101.0, 50.0], [101.0, 50.5]]
+            })
+        );
+        assert_eq!(path["properties"]["feature"], "path");
+        assert_eq!(path["properties"]["result"], 3);
+        assert_eq!(path["properties"]["commands"], 2);
+        assert_eq!(
+            (
+                &path["properties"]["position_x"],
+                &path["properties"]["position_y"]
+            ),
+            (&json!(2), &json!(1))
+        );
+        assert_eq!(path["properties"]["steps_moved"], 3);
+        assert_eq!(path["properties"]["max_x"], 2);
+        assert!(path["properties"].get("trace").is_none());
+
+        let cleaned = &geojson["features"][1];
+        assert_eq!(
+            cleaned["geometry"]["coordinates"],
+            json!([
+                [[
+                    [100.25, 49.75],
+                    [101.25, 49.75],
+                    [101.25, 50.25],
+                    [100.25, 50.25],
+                    [100.25, 49.75]
+                ]],
+                [[
+                    [100.75, 50.25],
+                    [101.25, 50.25],
+                    [101.25, 50.75],
+                    [100.75, 50.75],
+                    [100.75, 50.25]
+                ]]
+            ])
+        );
+        assert_eq!(cleaned["properties"]["feature"], "cleaned");
+        assert_eq!(cleaned["properties"]["result"], 3);
+    }
+
+    #[test]
+    fn rejects_invalid_cell_sizes() {
+        let origin: Origin = serde_json::from_str(r#"{"origin_x": 1.5}"#).unwrap();
+        assert_eq!((origin.x, origin.cell_size), (1.5, 1.0));
+
+        for cell_size in ["0", "-0.5", "1e999"] {
+            let query = format!(r#"{{"cell_size": {}}}"#, cell_size);
+            assert!(
+                serde_json::from_str::<Origin>(&query).is_err(),
+                "cell_size {} should be rejected",
+                cell_size
+            );
+        }
+    }
+}
@@ -0,0 +1,202 @@
+pub mod ascii;
+pub mod geojson;
+pub mod png;
+pub mod svg;
+
+use crate::{
+    execution::Simulation,
+    types::{Position, Region, FIELD_LIMIT},
+};
+
+/*  The renderers in this module draw pictures of an execution in different formats.
+    Depending on the format they show the vertices the robot has cleaned, the path it took
+    (as a polyline along the ends of its commands), where it started and ended, and the
+    obstacles it could have bumped into. The only obstacle the robot knows about is the
+    edge of the grid, which is why anything beyond `FIELD_LIMIT` is drawn as one.
+
+    The helpers here take care of the parts all of them need: the path itself and how to
+    fit it onto a canvas of limited size.
+*/
+
+/*  The path of the robot as the list of vertices it changed direction at, starting with
+    the `start` and ending with its final position. Consecutive duplicates (e.g. from
+    commands which didn't move the robot at all) are dropped.
+*/
+pub fn path(start: Position, simulation: &Simulation) -> Vec<Position> {
+    let mut path = vec![start];
+    for detail in &simulation.details {
+        if path.last() != Some(&detail.end) {
+            path.push(detail.end);
+        }
+    }
+
+    path
+}
+
+/*  The part of the grid a rendering has to show: the bounding box of the path (the
+    cleaned vertices are all on it) with a margin of one vertex around it, so markers and
+    the edge of the grid remain visible.
+*/
+pub fn extent(path: &[Position]) -> Region {
+    let first = path.first().copied().unwrap_or(Position { x: 0, y: 0 });
+    let region = path.iter().fold(
+        Region {
+            min: first,
+            max: first,
+        },
+        |region, position| region.including(*position),
+    );
+
+    Region {
+        min: Position {
+            x: region.min.x.saturating_sub(1),
+            y: region.min.y.saturating_sub(1),
+        },
+        max: Position {
+            x: region.max.x.saturating_add(1),
+            y: region.max.y.saturating_add(1),
+        },
+    }
+}
+
+// Whether a vertex is beyond the edge of the grid, i.e. an obstacle for the robot.
+pub fn is_obstacle(x: i64, y: i64) -> bool {
+    x.abs() > FIELD_LIMIT as i64 || y.abs() > FIELD_LIMIT as i64
+}
+
+/*  A `Viewport` maps vertices of the grid onto the pixels of a canvas which is at most
+    `size` pixels wide and high. Small paths are scaled up (each vertex becoming a square
+    of pixels), large ones are scaled down (with several vertices sharing a pixel), so
+    even a path spanning the whole grid fits onto the canvas.
+
+    Pixels are counted from the top left corner, while the grid's `y` axis points north,
+    which is why `y` is flipped.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub region: Region,
+    pub scale: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn fit(region: Region, size: u32, max_scale: f64) -> Self {
+        let columns = (region.max.x as i64 - region.min.x as i64 + 1) as f64;
+        let rows = (region.max.y as i64 - region.min.y as i64 + 1) as f64;
+        let scale = (size as f64 / columns.max(rows)).min(max_scale);
+
+        Viewport {
+            region,
+            scale,
+            width: ((columns * scale).ceil() as u32).clamp(1, size),
+            height: ((rows * scale).ceil() as u32).clamp(1, size),
+        }
+    }
+
+    // The pixel the top left corner of the vertex at `x`/`y` is mapped to.
+    pub fn pixel(&self, x: i64, y: i64) -> (u32, u32) {
+        let column = ((x - self.region.min.x as i64) as f64 * self.scale) as u32;
+        let row = ((self.region.max.y as i64 - y) as f64 * self.scale) as u32;
+
+        (column.min(self.width - 1), row.min(self.height - 1))
+    }
+
+    /*  The vertex shown at the top left corner of a pixel, which is the inverse of
+        `pixel`. Used for finding the obstacles within a canvas.
+    */
+    pub fn vertex(&self, column: u32, row: u32) -> (i64, i64) {
+        let x = self.region.min.x as i64 + (column as f64 / self.scale) as i64;
+        let y = self.region.max.y as i64 - (row as f64 / self.scale) as i64;
+
+        (x, y)
+    }
+
+    // How many pixels a single vertex covers along each axis (at least one).
+    pub fn cell(&self) -> u32 {
+        (self.scale.floor() as u32).max(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::Request,
+        execution::Execution,
+        types::{Command, Direction},
+    };
+
+    #[tokio::test]
+    async fn follows_path_along_commands() {
+        let start = Position { x: 0, y: 0 };
+        let request = Request {
+            start,
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 3,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 0,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 2,
+                },
+            ],
+        };
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let path = path(start, &simulation);
+
+        assert_eq!(
+            path,
+            vec![
+                Position { x: 0, y: 0 },
+                Position { x: 3, y: 0 },
+                Position { x: 3, y: 2 }
+            ]
+        );
+        assert_eq!(
+            extent(&path),
+            Region {
+                min: Position { x: -1, y: -1 },
+                max: Position { x: 4, y: 3 },
+            }
+        );
+    }
+
+    #[test]
+    fn fits_viewport() {
+        let small = Viewport::fit(
+            Region {
+                min: Position { x: -1, y: -1 },
+                max: Position { x: 4, y: 3 },
+            },
+            512,
+            16.0,
+        );
+        assert_eq!((small.width, small.height, small.cell()), (96, 80, 16));
+        assert_eq!(small.pixel(0, 0), (16, 48));
+
+        let limit = FIELD_LIMIT + 1;
+        let large = Viewport::fit(
+            Region {
+                min: Position {
+                    x: -limit,
+                    y: -limit,
+                },
+                max: Position { x: limit, y: limit },
+            },
+            512,
+            16.0,
+        );
+        assert_eq!((large.width, large.height, large.cell()), (512, 512, 1));
+        assert_eq!(large.pixel(limit as i64, -limit as i64), (511, 511));
+        assert!(is_obstacle(large.vertex(0, 0).0, large.vertex(0, 0).1));
+    }
+}
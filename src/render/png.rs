@@ -0,0 +1,230 @@
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+
+use super::{extent, is_obstacle, path, Viewport};
+use crate::{execution::Simulation, types::Position};
+
+// The largest width and height of a rendered image, in pixels
+pub const SIZE: u32 = 1024;
+
+// The largest number of pixels a single vertex is scaled up to
+pub const SCALE: f64 = 16.0;
+
+type Color = [u8; 3];
+
+const BACKGROUND: Color = [255, 255, 255];
+const OBSTACLE: Color = [64, 64, 64];
+const CLEANED: Color = [170, 210, 240];
+const PATH: Color = [20, 70, 160];
+const START: Color = [30, 160, 60];
+const END: Color = [210, 40, 40];
+
+/*  Renders the `Simulation` of a robot which started at `start` as a PNG image (see the
+    module documentation for what it shows). There is no randomness involved and the
+    vertices are drawn in a fixed order, so the same `Simulation` always yields the same
+    bytes, which is what the snapshot test below relies on.
+*/
+pub fn render(start: Position, simulation: &Simulation) -> Result<Vec<u8>, EncodingError> {
+    let path = path(start, simulation);
+    let viewport = Viewport::fit(extent(&path), SIZE, SCALE);
+    let mut canvas = Canvas::new(viewport);
+
+    for row in 0..viewport.height {
+        for column in 0..viewport.width {
+            let (x, y) = viewport.vertex(column, row);
+            if is_obstacle(x, y) {
+                canvas.set(column, row, OBSTACLE);
+            }
+        }
+    }
+
+    for position in simulation.visits.keys() {
+        canvas.fill(*position, 0, CLEANED);
+    }
+
+    for line in path.windows(2) {
+        canvas.line(line[0], line[1], PATH);
+    }
+
+    let marker = viewport.cell().max(5) / 2;
+    canvas.fill(start, marker, START);
+    canvas.fill(*path.last().unwrap(), marker, END);
+
+    canvas.encode()
+}
+
+/*  A `Canvas` is a plain buffer of RGB pixels, with a few functions for drawing vertices
+    and lines of the grid onto it.
+*/
+struct Canvas {
+    viewport: Viewport,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(viewport: Viewport) -> Self {
+        let pixels = BACKGROUND
+            .iter()
+            .copied()
+            .cycle()
+            .take((viewport.width * viewport.height * 3) as usize)
+            .collect();
+
+        Canvas { viewport, pixels }
+    }
+
+    fn set(&mut self, column: u32, row: u32, color: Color) {
+        if column < self.viewport.width && row < self.viewport.height {
+            let index = ((row * self.viewport.width + column) * 3) as usize;
+            self.pixels[index..index + 3].copy_from_slice(&color);
+        }
+    }
+
+    /*  Fills all the pixels covered by the vertex at `position`, growing it by `margin`
+        pixels into every direction (which is how the markers are drawn).
+    */
+    fn fill(&mut self, position: Position, margin: u32, color: Color) {
+        let (column, row) = self.viewport.pixel(position.x as i64, position.y as i64);
+        let size = self.viewport.cell();
+        let center = (column + size / 2, row + size / 2);
+
+        let (left, top) = if margin > 0 {
+            (
+                center.0.saturating_sub(margin),
+                center.1.saturating_sub(margin),
+            )
+        } else {
+            (column, row)
+        };
+        let (right, bottom) = if margin > 0 {
+            (center.0 + margin, center.1 + margin)
+        } else {
+            (column + size - 1, row + size - 1)
+        };
+
+        for row in top..=bottom {
+            for column in left..=right {
+                self.set(column, row, color);
+            }
+        }
+    }
+
+    // Draws a line between the centers of two vertices (Bresenham's line algorithm).
+    fn line(&mut self, from: Position, to: Position, color: Color) {
+        let half = self.viewport.cell() / 2;
+        let (x0, y0) = self.viewport.pixel(from.x as i64, from.y as i64);
+        let (x1, y1) = self.viewport.pixel(to.x as i64, to.y as i64);
+        let (mut x, mut y) = ((x0 + half) as i64, (y0 + half) as i64);
+        let (x1, y1) = ((x1 + half) as i64, (y1 + half) as i64);
+
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            self.set(x as u32, y as u32, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    fn encode(self) -> Result<Vec<u8>, EncodingError> {
+        let mut bytes = vec![];
+
+        let mut encoder = Encoder::new(&mut bytes, self.viewport.width, self.viewport.height);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        api::Request,
+        execution::Execution,
+        types::{Command, Direction, FIELD_LIMIT},
+    };
+
+    /*  Compares the rendering of one of the example requests against a PNG checked into
+        the fixtures. If the rendering is changed on purpose the fixture needs to be replaced.
+    */
+    #[tokio::test]
+    async fn renders_snapshot() {
+        let file = fs::read_to_string("test/fixtures/example_request_positive.json")
+            .expect("Unable to read file");
+        let request: Request = serde_json::from_str(&file).unwrap();
+        let start = request.start;
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let image = render(start, &simulation).unwrap();
+
+        let snapshot =
+            fs::read("test/fixtures/example_render_positive.png").expect("Unable to read file");
+        assert!(image == snapshot, "rendering differs from snapshot");
+    }
+
+    /*  A robot going from one corner of the grid to the other (and running into its edges
+        on the way), which is as large as a rendering can get.
+    */
+    #[tokio::test]
+    async fn renders_full_grid() {
+        let request = Request {
+            start: Position {
+                x: -FIELD_LIMIT,
+                y: -FIELD_LIMIT,
+            },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 2 * FIELD_LIMIT + 10,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 2 * FIELD_LIMIT + 10,
+                },
+            ],
+        };
+        let start = request.start;
+
+        let (execution, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        assert_eq!(
+            execution.position(),
+            Some(Position {
+                x: FIELD_LIMIT,
+                y: FIELD_LIMIT
+            })
+        );
+        let image = render(start, &simulation).unwrap();
+        assert_eq!(image, render(start, &simulation).unwrap());
+
+        let info = png::Decoder::new(image.as_slice())
+            .read_info()
+            .unwrap()
+            .info()
+            .clone();
+        assert!(info.width <= SIZE && info.height <= SIZE);
+        assert!(info.width == SIZE || info.height == SIZE);
+    }
+}
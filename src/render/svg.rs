@@ -0,0 +1,129 @@
+use std::fmt::Write;
+
+use super::{extent, path};
+use crate::{
+    execution::Simulation,
+    types::{Command, Direction, Position},
+};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West,
+];
+
+/*  The color of the lines drawn for each `Direction`, so it's possible to tell which way
+    the robot went along a line even without looking at its arrow.
+*/
+fn color(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::North => "#1f77b4",
+        Direction::East => "#2ca02c",
+        Direction::South => "#ff7f0e",
+        Direction::West => "#9467bd",
+    }
+}
+
+fn name(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::North => "north",
+        Direction::East => "east",
+        Direction::South => "south",
+        Direction::West => "west",
+    }
+}
+
+/*  Renders the `Simulation` of a robot which started at `start` as an SVG image, with one
+    polyline (ending in an arrow) for every one of its `commands`. Hovering over a line shows
+    the index of its `Command`, together with what it told the robot to do.
+
+    Unlike the PNG rendering the SVG uses the coordinates of the grid itself (flipping `y`,
+    since the `y` axis of an SVG points south) and leaves the scaling to whoever displays it,
+    which is why it's possible to zoom into even the longest paths without losing detail.
+    The strokes don't scale, so the lines stay visible at any zoom level.
+*/
+pub fn render(start: Position, commands: &[Command], simulation: &Simulation) -> String {
+    let region = extent(&path(start, simulation));
+    let mut svg = String::new();
+
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        region.min.x,
+        -region.max.y,
+        region.max.x as i64 - region.min.x as i64,
+        region.max.y as i64 - region.min.y as i64,
+    )
+    .unwrap();
+
+    svg.push_str("<defs>");
+    for direction in DIRECTIONS.iter() {
+        write!(
+            svg,
+            r#"<marker id="arrow-{}" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="4" markerHeight="4" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="{}"/></marker>"#,
+            name(direction),
+            color(direction),
+        )
+        .unwrap();
+    }
+    svg.push_str("</defs>");
+
+    for (index, (command, detail)) in commands.iter().zip(&simulation.details).enumerate() {
+        write!(
+            svg,
+            r#"<polyline points="{},{} {},{}" fill="none" stroke="{}" stroke-width="2" vector-effect="non-scaling-stroke" marker-end="url(#arrow-{})"><title>Command {}: {} {} steps</title></polyline>"#,
+            detail.start.x,
+            -detail.start.y,
+            detail.end.x,
+            -detail.end.y,
+            color(&command.direction),
+            name(&command.direction),
+            index,
+            name(&command.direction),
+            command.steps,
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api::Request, execution::Execution};
+
+    #[tokio::test]
+    async fn renders_polyline_per_command() {
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![
+                Command {
+                    direction: Direction::East,
+                    steps: 3,
+                },
+                Command {
+                    direction: Direction::North,
+                    steps: 2,
+                },
+            ],
+        };
+        let commands = request.commands.clone();
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let svg = render(Position { x: 0, y: 0 }, &commands, &simulation);
+
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -3 5 4">"#));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert!(svg.contains(
+            r##"<polyline points="0,0 3,0" fill="none" stroke="#2ca02c" stroke-width="2" vector-effect="non-scaling-stroke" marker-end="url(#arrow-east)"><title>Command 0: east 3 steps</title></polyline>"##
+        ));
+        assert!(svg.contains(r#"points="3,0 3,-2""#));
+        assert!(svg.contains("<title>Command 1: north 2 steps</title>"));
+        assert!(svg.ends_with("</svg>"));
+    }
+}
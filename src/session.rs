@@ -0,0 +1,309 @@
+use axum::extract::ws::{Message, WebSocket};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+use std::time::Instant;
+
+use crate::{
+    api::{self, Response},
+    clock::Clock,
+    error::Error,
+    execution::{Execution, Robot, Simulation},
+    format::{Format, Syntax},
+    patterns::{Instruction, Plan},
+    types::{Command, Position},
+    validation::{validate, Issue, MAX_COMMANDS},
+};
+
+/*  A `Step` is what the server replies to every message of a session: the number of the
+    `command` (`0` for the start), the new `position` of the robot, the vertices the command
+    has `cleaned` for the first time, the ones it has `recleaned`, the steps it has dropped
+    at the edge of the grid (`clamped`) and the number of vertices cleaned so far (`result`).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Step {
+    pub command: usize,
+    pub position: Position,
+    pub cleaned: i64,
+    pub recleaned: i64,
+    pub clamped: i64,
+    pub result: i64,
+}
+
+/*  A `Session` is a robot controlled by hand, one command at a time. The first message
+    of a session is the start `Position` of the robot (e.g. `{"x": 0, "y": 0}`), every other
+    one a `Command` (e.g. `{"direction": "east", "steps": 2}`). Both are validated just like
+    a `Request`, and a message which isn't valid doesn't end the session, it's only answered
+    with an `Error` instead of a `Step`. The session ends with a `Finish` message.
+*/
+#[derive(Debug, Default)]
+pub struct Session {
+    robot: Option<Robot>,
+}
+
+impl Session {
+    pub fn handle(&mut self, message: &[u8]) -> Result<Step, Error> {
+        let robot = match &mut self.robot {
+            Some(robot) => robot,
+            None => {
+                let start: Position = parse(message)?;
+                check(&Plan {
+                    start,
+                    commands: vec![],
+                })?;
+
+                let robot = Robot::new(start);
+                let step = Step {
+                    command: 0,
+                    position: start,
+                    cleaned: 0,
+                    recleaned: 0,
+                    clamped: 0,
+                    result: robot.cleaned(),
+                };
+                self.robot = Some(robot);
+                return Ok(step);
+            }
+        };
+
+        let command: Command = parse(message)?;
+        if robot.commands() >= MAX_COMMANDS {
+            return Err(Error::Validation(vec![Issue {
+                path: "$".to_string(),
+                message: format!("Session has reached the limit of {} commands", MAX_COMMANDS),
+            }]));
+        }
+        check(&Plan {
+            start: robot.position(),
+            commands: vec![Instruction::Command(command.clone())],
+        })?;
+
+        let detail = robot.execute(command).clone();
+        Ok(Step {
+            command: robot.commands(),
+            position: detail.end,
+            cleaned: detail.cleaned,
+            recleaned: detail.recleaned,
+            clamped: detail.clamped,
+            result: robot.cleaned(),
+        })
+    }
+
+    // The whole run of the session, unless it never got a start `Position`.
+    pub fn finish(self) -> Option<(Execution, Simulation)> {
+        self.robot.map(|robot| robot.finish(Execution::default()))
+    }
+}
+
+/*  The message which ends a session (`{"finish": true}`), so the client gets to know the
+    `Execution` it has been saved as before the connection is closed.
+*/
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Finish {
+    finish: bool,
+}
+
+// Whether the message asks to end the session.
+fn finishes(message: &[u8]) -> bool {
+    matches!(parse(message), Ok(Finish { finish: true }))
+}
+
+fn parse<T: DeserializeOwned>(message: &[u8]) -> Result<T, Error> {
+    Syntax::Json
+        .parse(message)
+        .map_err(|(message, line)| Error::Deserialization { message, line })
+}
+
+/*  Validates a single message as a `Plan`, whose paths (e.g. `$.commands[0].steps`) are
+    cut down to the message itself (`$.steps`).
+*/
+fn check(plan: &Plan) -> Result<(), Error> {
+    let errors: Vec<Issue> = validate(plan)
+        .errors
+        .into_iter()
+        .map(|issue| Issue {
+            path: issue
+                .path
+                .replacen("$.commands[0]", "$", 1)
+                .replacen("$.start", "$", 1),
+            message: issue.message,
+        })
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
+    }
+
+    Ok(())
+}
+
+/*  Saves the whole run of a `Session` as an `Execution`, just as if it had been sent to
+    `/path` in one go (so it can be replayed later on), and answers with its `Response` (just
+    like `/executions/<id>`).
+    A session which never got a start `Position` has nothing to save.
+*/
+pub async fn save(
+    session: Session,
+    state: Pool<Postgres>,
+    clock: &Clock,
+) -> Result<Response, Error> {
+    let (execution, _) = session.finish().ok_or_else(|| {
+        Error::Unprocessable("Session has no start position, there's nothing to save".to_string())
+    })?;
+
+    let start_time = Instant::now();
+    let execution = execution.save(state.clone()).await?;
+    let response = Response::localized(execution, clock)
+        .with_persistence(start_time.elapsed())
+        .with_serialization(Format::Json);
+    api::record_timings(state, &response);
+
+    Ok(response)
+}
+
+/*  Runs a `Session` over a WebSocket, replying to every message with a `Step` or an `Error`,
+    both as JSON text. Once the client sends a `Finish` message, the session is saved and
+    the reply is the `Response` (including the `id` of the `Execution`), after which the
+    server closes the connection. A session the client closes (or goes away from) is saved
+    all the same, there's just nobody left to tell the `id` to.
+
+    A single command takes a few milliseconds at most (see `MAX_STEPS`), so the robot is
+    moved right here instead of on a thread of its own.
+*/
+pub async fn run(
+    mut socket: WebSocket,
+    state: Pool<Postgres>,
+    clock: Clock,
+    correlation_id: String,
+) {
+    let mut session = Session::default();
+    let mut finished = false;
+
+    while let Some(message) = socket.recv().await {
+        let message = match message {
+            Ok(Message::Text(text)) => text.into_bytes(),
+            Ok(Message::Binary(bytes)) => bytes,
+            Ok(Message::Close(_)) | Err(_) => break,
+            Ok(_) => continue,
+        };
+        if finishes(&message) {
+            finished = true;
+            break;
+        }
+
+        let reply: Value = match session.handle(&message) {
+            Ok(step) => json!(step),
+            Err(error) => error.body(correlation_id.clone()),
+        };
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
+        }
+    }
+
+    let reply: Value = match save(session, state, &clock).await {
+        Ok(response) => {
+            tracing::info!(%correlation_id, "Saved session as execution {:?}", response.id());
+            json!(response)
+        }
+        Err(Error::Unprocessable(message)) if !finished => {
+            tracing::debug!(%correlation_id, "{}", message);
+            return;
+        }
+        Err(error) => {
+            tracing::error!(%correlation_id, kind = error.kind(), "{:?}", error);
+            error.body(correlation_id)
+        }
+    };
+
+    if finished {
+        let _ = socket.send(Message::Text(reply.to_string())).await;
+        let _ = socket.send(Message::Close(None)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moves_robot_one_command_at_a_time() {
+        let mut session = Session::default();
+
+        let step = session.handle(br#"{"x": 0, "y": 0}"#).unwrap();
+        assert_eq!((step.command, step.position), (0, Position { x: 0, y: 0 }));
+
+        let step = session
+            .handle(br#"{"direction": "east", "steps": 2}"#)
+            .unwrap();
+        assert_eq!(
+            step,
+            Step {
+                command: 1,
+                position: Position { x: 2, y: 0 },
+                cleaned: 2,
+                recleaned: 0,
+                clamped: 0,
+                result: 2,
+            }
+        );
+
+        match session.handle(br#"{"direction": "west", "steps": -1}"#) {
+            Err(Error::Validation(violations)) => assert_eq!(violations[0].path, "$.steps"),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+
+        let step = session
+            .handle(br#"{"direction": "west", "steps": 1}"#)
+            .unwrap();
+        assert_eq!((step.cleaned, step.recleaned, step.result), (0, 1, 2));
+
+        let (execution, _) = session.finish().unwrap();
+        assert_eq!((execution.commands, execution.result), (2, 2));
+        assert_eq!(execution.request.unwrap().0.commands.len(), 2);
+    }
+
+    #[test]
+    fn rejects_start_outside_of_grid() {
+        let mut session = Session::default();
+
+        match session.handle(br#"{"x": 100001, "y": 0}"#) {
+            Err(Error::Validation(violations)) => assert_eq!(violations[0].path, "$.x"),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+        assert!(session.finish().is_none());
+    }
+
+    #[test]
+    fn tells_finish_messages() {
+        assert!(finishes(br#"{"finish": true}"#));
+        assert!(!finishes(br#"{"finish": false}"#));
+        assert!(!finishes(br#"{"finish": true, "steps": 1}"#));
+        assert!(!finishes(br#"{"direction": "east", "steps": 2}"#));
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database in DATABASE_URL"]
+    async fn saves_finished_sessions(pool: Pool<Postgres>) {
+        let mut session = Session::default();
+        session.handle(br#"{"x": 0, "y": 0}"#).unwrap();
+        session
+            .handle(br#"{"direction": "north", "steps": 3}"#)
+            .unwrap();
+
+        let response = save(session, pool.clone(), &Clock::default())
+            .await
+            .unwrap();
+        let execution = Execution::find(pool.clone(), response.id().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((execution.commands, execution.result), (1, 3));
+
+        match save(Session::default(), pool, &Clock::default()).await {
+            Err(Error::Unprocessable(_)) => {}
+            other => panic!("Unexpected outcome {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{
+    async_trait,
+    body::HttpBody,
+    extract::FromRequest,
+    http::Request,
+    response::{IntoResponse, Response as HttpResponse},
+    BoxError,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/*  Wall clock time can jump (e.g. when the server synchronizes its clock), which makes it
+    unfit for measuring how long something took. All of our timings are therefore taken
+    with an `Instant`, which comes from a monotonic clock, and are kept in nanoseconds.
+*/
+pub fn nanos(duration: Duration) -> i64 {
+    duration.as_nanos().try_into().unwrap_or(i64::MAX)
+}
+
+/*  `Timings` are the phases handling a request went through, in nanoseconds:
+
+    - `parse`: reading and deserializing the request body (a batch splits the time it took
+      for its whole body evenly among its requests)
+    - `simulation`: moving the robot (which is what `duration` has always been about)
+    - `persistence`: saving the `Execution` to the database
+    - `serialization`: encoding the `Response` (see `Response::with_serialization`)
+
+    `parse` and `simulation` are saved along with the `Execution`. The other two are only
+    over once it has been saved, so they are recorded afterwards (see `record_timings` in
+    `api.rs`).
+
+    Each of them is "Option"al, executions stored before they were introduced don't have
+    any, and executions which didn't come from a request body (replays and sessions) don't
+    have a `parse` phase. Jobs don't answer with their `Execution`, so they don't have a
+    `serialization` phase.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub parse: Option<i64>,
+    pub simulation: Option<i64>,
+    pub persistence: Option<i64>,
+    pub serialization: Option<i64>,
+}
+
+/*  `Timed` wraps any other extractor and measures how long it took to extract it, e.g.
+    `Timed(Payload(request), parse)` for the time it took to read and parse a request body.
+*/
+pub struct Timed<T>(pub T, pub Duration);
+
+#[async_trait]
+impl<S, B, T> FromRequest<S, B> for Timed<T>
+where
+    T: FromRequest<S, B>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = HttpResponse;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let start = Instant::now();
+        let value = T::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(Timed(value, start.elapsed()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::header};
+
+    use crate::{api::Request as PathRequest, format::Payload};
+
+    #[tokio::test]
+    async fn times_extraction() {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                include_str!("../test/fixtures/example_request_positive.json").to_string(),
+            ))
+            .unwrap();
+
+        let Timed(Payload(request), duration) =
+            Timed::<Payload<PathRequest>>::from_request(request, &())
+                .await
+                .ok()
+                .unwrap();
+
+        assert_eq!(request.commands.len(), 2);
+        assert!(duration > Duration::ZERO);
+        assert_eq!(nanos(Duration::from_micros(3)), 3000);
+    }
+}
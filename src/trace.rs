@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::types::{Direction, Position};
+
+/*  A `Segment` is a straight line the robot has actually driven: from `from` to `to` in a
+    single `Direction`, having `moved` that many steps and dropped `clamped` steps at the
+    edge of the grid (which always happens at the end of a segment, once the robot is stuck
+    there it stays stuck until it turns).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Segment {
+    pub from: Position,
+    pub to: Position,
+    pub direction: Direction,
+    pub moved: i64,
+    pub clamped: i64,
+}
+
+/*  A `Trace` is the sequence of `Segment`s the robot has driven, which is enough to
+    reconstruct exactly what it did without running the request again.
+
+    It only contains what was effective: commands without any steps don't show up at all,
+    and consecutive commands going into the same `Direction` are combined into a single
+    `Segment`. The latter also means that a robot stuck at the edge of the grid, being told
+    to go on again and again, only ever yields one `Segment` (with all of its steps clamped).
+
+    It's serialized as a plain array of `Segment`s.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Trace(pub Vec<Segment>);
+
+impl Trace {
+    // Appends a `Segment`, combining it with the last one if it goes into the same `Direction`.
+    pub fn push(&mut self, segment: Segment) {
+        if segment.moved == 0 && segment.clamped == 0 {
+            return;
+        }
+
+        match self.0.last_mut() {
+            Some(last) if last.direction == segment.direction => {
+                last.to = segment.to;
+                last.moved += segment.moved;
+                last.clamped += segment.clamped;
+            }
+            _ => self.0.push(segment),
+        }
+    }
+}
+
+/*  A `Trace` is stored as a short piece of text rather than JSON, since most of a `Segment`
+    can be derived from the one before it. It's the starting position followed by one token
+    per `Segment`: the first letter of its `Direction`, the steps it has `moved` and, if there
+    were any, a `+` with the steps it has `clamped`, e.g. `10,22 E2 N1 W0+5`.
+
+    An empty `Trace` is stored as an empty text.
+*/
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let first = match self.0.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+
+        write!(f, "{},{}", first.from.x, first.from.y)?;
+        for segment in &self.0 {
+            let letter = match segment.direction {
+                Direction::North => 'N',
+                Direction::East => 'E',
+                Direction::South => 'S',
+                Direction::West => 'W',
+            };
+            write!(f, " {}{}", letter, segment.moved)?;
+            if segment.clamped > 0 {
+                write!(f, "+{}", segment.clamped)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tokens = text.split_whitespace();
+        let start = match tokens.next() {
+            Some(start) => start,
+            None => return Ok(Trace::default()),
+        };
+
+        let invalid = |token: &str| format!("Invalid trace token '{}'", token);
+        let (x, y) = start.split_once(',').ok_or_else(|| invalid(start))?;
+        let mut position = Position {
+            x: x.parse().map_err(|_| invalid(start))?,
+            y: y.parse().map_err(|_| invalid(start))?,
+        };
+
+        let mut segments = vec![];
+        for token in tokens {
+            let direction = match token.chars().next() {
+                Some('N') => Direction::North,
+                Some('E') => Direction::East,
+                Some('S') => Direction::South,
+                Some('W') => Direction::West,
+                _ => return Err(invalid(token)),
+            };
+            let (moved, clamped) = token[1..].split_once('+').unwrap_or((&token[1..], "0"));
+            let moved: i64 = moved.parse().map_err(|_| invalid(token))?;
+            let clamped: i64 = clamped.parse().map_err(|_| invalid(token))?;
+
+            let to = position.travel(&direction, moved.try_into().map_err(|_| invalid(token))?);
+            segments.push(Segment {
+                from: position,
+                to,
+                direction,
+                moved,
+                clamped,
+            });
+            position = to;
+        }
+
+        Ok(Trace(segments))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::Request,
+        execution::Execution,
+        types::{Command, FIELD_LIMIT},
+    };
+
+    fn command(direction: Direction, steps: i32) -> Command {
+        Command { direction, steps }
+    }
+
+    #[tokio::test]
+    async fn collapses_segments() {
+        let request = Request {
+            start: Position {
+                x: FIELD_LIMIT - 2,
+                y: 0,
+            },
+            commands: vec![
+                command(Direction::North, 1),
+                command(Direction::North, 2),
+                command(Direction::West, 0),
+                command(Direction::East, 5),
+                command(Direction::East, 4),
+                command(Direction::South, 3),
+            ],
+        };
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let trace = simulation.trace;
+
+        assert_eq!(
+            trace.0,
+            vec![
+                Segment {
+                    from: Position {
+                        x: FIELD_LIMIT - 2,
+                        y: 0
+                    },
+                    to: Position {
+                        x: FIELD_LIMIT - 2,
+                        y: 3
+                    },
+                    direction: Direction::North,
+                    moved: 3,
+                    clamped: 0,
+                },
+                Segment {
+                    from: Position {
+                        x: FIELD_LIMIT - 2,
+                        y: 3
+                    },
+                    to: Position {
+                        x: FIELD_LIMIT,
+                        y: 3
+                    },
+                    direction: Direction::East,
+                    moved: 2,
+                    clamped: 7,
+                },
+                Segment {
+                    from: Position {
+                        x: FIELD_LIMIT,
+                        y: 3
+                    },
+                    to: Position {
+                        x: FIELD_LIMIT,
+                        y: 0
+                    },
+                    direction: Direction::South,
+                    moved: 3,
+                    clamped: 0,
+                },
+            ]
+        );
+        assert_eq!(trace.to_string(), "99998,0 N3 E2+7 S3");
+    }
+
+    #[tokio::test]
+    async fn decodes_stored_trace() {
+        let request = Request {
+            start: Position { x: -3, y: 7 },
+            commands: vec![
+                command(Direction::West, 2),
+                command(Direction::South, 10),
+                command(Direction::East, 1),
+            ],
+        };
+
+        let (_, simulation) = Execution::default()
+            .calculate(request, |_| true)
+            .unwrap_or_default();
+        let text = simulation.trace.to_string();
+
+        assert_eq!(text, "-3,7 W2 S10 E1");
+        assert_eq!(text.parse::<Trace>().unwrap(), simulation.trace);
+        assert_eq!("".parse::<Trace>().unwrap(), Trace::default());
+        assert!("1,2 X3".parse::<Trace>().is_err());
+    }
+}
@@ -32,11 +32,52 @@ impl Position {
         self
     }
 
+    /*  This is the shortcut version of calling `shift` over and over again: it moves the
+        `Position` a number of steps into a `Direction` in one go, stopping at the edge of
+        the grid exactly like `shift` would.
+
+        `shift` only takes a step if it ends up on the grid, so a robot just outside of it
+        can still move if its first step brings it back (e.g. from `x = 100001` to the
+        `West`), while one further out can't move at all. After the first step the robot is
+        on the grid and all that's left is stopping it at the edge. The steps are added up
+        as `i64`, so nothing overflows, no matter where the robot starts from.
+    */
+    pub fn travel(self, direction: &Direction, steps: i32) -> Self {
+        if steps <= 0 {
+            return self;
+        }
+
+        let first = self.shift(direction);
+        if first == self {
+            return self;
+        }
+
+        let movement = Position::from(direction);
+        let limit = FIELD_LIMIT as i64;
+        let destination = |value: i32, movement: i32| {
+            (value as i64 + movement as i64 * (steps as i64 - 1)).clamp(-limit, limit) as i32
+        };
+
+        Position {
+            x: destination(first.x, movement.x),
+            y: destination(first.y, movement.y),
+        }
+    }
+
+    // Moves the `Position` back onto the grid should it be placed outside of it.
+    pub fn clamped(self) -> Self {
+        Position {
+            x: self.x.clamp(-FIELD_LIMIT, FIELD_LIMIT),
+            y: self.y.clamp(-FIELD_LIMIT, FIELD_LIMIT),
+        }
+    }
+
     /*  The function just checks whether the field limit has been reached for any point
-        of the `Position`.
+        of the `Position`. `unsigned_abs` is used because `i32::MIN` has no positive
+        counterpart as an `i32`.
     */
-    fn out_of_bounds(&self) -> bool {
-        self.x.abs() > FIELD_LIMIT || self.y.abs() > FIELD_LIMIT
+    pub fn out_of_bounds(&self) -> bool {
+        self.x.unsigned_abs() > FIELD_LIMIT as u32 || self.y.unsigned_abs() > FIELD_LIMIT as u32
     }
 }
 
@@ -44,15 +85,16 @@ impl Position {
     moves! It adds the position it gets passed to in the `shift` function to the position
     it already has stored in its representation of `self`. Since we're just dealing with
     coordinates in a 2D grid it's a simple matter of adding the relevant coordinates together
-    and returning the resulting `Position` as `self`.
+    and returning the resulting `Position` as `self`. The coordinates saturate at the limits
+    of `i32` (far beyond the grid) instead of overflowing.
 */
 impl Add for Position {
     type Output = Self;
 
     fn add(self, position: Self) -> Self {
         Self {
-            x: self.x + position.x,
-            y: self.y + position.y,
+            x: self.x.saturating_add(position.x),
+            y: self.y.saturating_add(position.y),
         }
     }
 }
@@ -62,6 +104,8 @@ impl Add for Position {
     serialization/deserialization library `serde` here that it should expect each `enum`
     representation to be in `lowercase` instead of the representation chosen by the `enum`.
     It's a common convention in Rust to write `enum`s as `PascalCase`.
+
+    The order of the directions is part of `Request::hash`, new ones go at the end.
 */
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -99,6 +143,51 @@ pub struct Command {
     pub steps: i32,
 }
 
+/*  A `Region` is a rectangle on the grid, described by its lower left (`min`) and its
+    upper right (`max`) corner. Both corners are part of the `Region`.
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Region {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl Region {
+    /*  Users might mix up the corners, and they might describe a `Region` reaching beyond
+        the edge of the grid. This function returns a `Region` with its corners in the right
+        order, cut down to the part of it that is actually on the grid.
+    */
+    pub fn normalized(self) -> Self {
+        let min = Position {
+            x: self.min.x.min(self.max.x),
+            y: self.min.y.min(self.max.y),
+        };
+        let max = Position {
+            x: self.min.x.max(self.max.x),
+            y: self.min.y.max(self.max.y),
+        };
+
+        Region {
+            min: min.clamped(),
+            max: max.clamped(),
+        }
+    }
+
+    // Grows the `Region` just enough to include `position`.
+    pub fn including(self, position: Position) -> Self {
+        Region {
+            min: Position {
+                x: self.min.x.min(position.x),
+                y: self.min.y.min(position.y),
+            },
+            max: Position {
+                x: self.max.x.max(position.x),
+                y: self.max.y.max(position.y),
+            },
+        }
+    }
+}
+
 /*  The test coverage here concerns itself with movements, in any direction, to ensure
     the robot can move and will move into relevant directions when told so.
 
@@ -131,6 +220,78 @@ mod test {
         assert_eq!(position, Position { x: -1, y: 0 })
     }
 
+    #[test]
+    fn travels_with_boundary() {
+        let position = Position { x: 0, y: 0 };
+
+        assert_eq!(
+            position.travel(&Direction::North, 10),
+            Position { x: 0, y: 10 }
+        );
+        assert_eq!(
+            position.travel(&Direction::West, 120000),
+            Position { x: -100000, y: 0 }
+        );
+        assert_eq!(position.travel(&Direction::South, -5), position);
+
+        let position = Position { x: 100002, y: 0 };
+        assert_eq!(position.travel(&Direction::West, 10), position);
+
+        let position = Position { x: 100001, y: 0 };
+        assert_eq!(
+            position.travel(&Direction::West, 10),
+            Position { x: 99991, y: 0 }
+        );
+
+        let position = Position { x: i32::MAX, y: 0 };
+        assert_eq!(position.travel(&Direction::East, i32::MAX), position);
+        assert_eq!(position.shift(&Direction::East), position);
+
+        let position = Position {
+            x: -FIELD_LIMIT,
+            y: 0,
+        };
+        assert_eq!(
+            position.travel(&Direction::East, i32::MAX),
+            Position {
+                x: FIELD_LIMIT,
+                y: 0
+            }
+        );
+    }
+
+    #[test]
+    fn tells_positions_out_of_bounds() {
+        assert!(!Position {
+            x: -FIELD_LIMIT,
+            y: FIELD_LIMIT
+        }
+        .out_of_bounds());
+        assert!(Position {
+            x: FIELD_LIMIT + 1,
+            y: 0
+        }
+        .out_of_bounds());
+        assert!(Position { x: i32::MIN, y: 0 }.out_of_bounds());
+        assert!(Position { x: 0, y: i32::MIN }.out_of_bounds());
+    }
+
+    #[test]
+    fn normalizes_regions() {
+        let region = Region {
+            min: Position { x: 5, y: 120000 },
+            max: Position { x: -5, y: 3 },
+        };
+
+        assert_eq!(
+            region.normalized(),
+            Region {
+                min: Position { x: -5, y: 3 },
+                max: Position { x: 5, y: 100000 },
+            }
+        );
+    }
+
     #[test]
     fn adds_positions() {
         let a = Position { x: 10, y: 5 };
@@ -0,0 +1,636 @@
+use axum::{
+    async_trait, body::HttpBody, extract::FromRequest, http::Request as HttpRequest, BoxError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    format::{columns, Columns, Payload},
+    patterns::{Instruction, Pattern, Plan, MAX_BOUNCE_STEPS, MAX_SPOT_RADIUS},
+    types::{Command, Position, FIELD_LIMIT},
+};
+
+// The largest number of commands a robot can be sent at once
+pub const MAX_COMMANDS: usize = 10000;
+
+// The largest number of steps of a single command, which is enough to cross half the grid
+pub const MAX_STEPS: i32 = 100000;
+
+/*  The number of steps from which on a simulation takes noticeably long (every step is an
+    iteration of the simulation, which is what its cost mostly depends on).
+*/
+pub const COSTLY_STEPS: i64 = 10000000;
+
+/*  The largest number of steps a simulation may take. Every field the robot visits is kept
+    in memory until it's done, so this is what keeps a single request from taking up all
+    of it (plain commands alone could add up to a billion steps).
+*/
+pub const MAX_COST: i64 = 50000000;
+
+/*  An `Issue` is something wrong with a `Request` (or something that looks like it), along
+    with the JSON path of the field it's about (e.g. `$.commands[3].steps`).
+
+    Requests are validated before their patterns are expanded (see `Plan`), so the index of
+    a command is its index in the request the client has sent, and issues with a pattern
+    point into it (e.g. `$.commands[3].spot.radius`).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Issue {
+    pub path: String,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Issue {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/*  A `Report` is what we know about a `Request` without simulating it: the number of
+    `commands` (and patterns) it was sent with, the sum of the steps the robot would be told
+    to take (`steps_requested`, including the ones of expanded patterns) and the smallest and
+    largest step count of the plain commands, whether the robot starts on the grid, and an
+    `estimated_cost` of the simulation (the number of steps it would have to take, moving or
+    not).
+
+    `errors` are what makes the `Request` invalid, `warnings` what most likely isn't
+    intended (e.g. commands without any steps or steps dropped at the edge of the grid).
+*/
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Report {
+    pub commands: usize,
+    pub steps_requested: i64,
+    pub min_steps: Option<i32>,
+    pub max_steps: Option<i32>,
+    pub start_in_bounds: bool,
+    pub estimated_cost: i64,
+    pub warnings: Vec<Issue>,
+    pub errors: Vec<Issue>,
+}
+
+impl Columns for Report {
+    fn columns() -> Vec<String> {
+        columns(&[
+            "commands",
+            "steps_requested",
+            "min_steps",
+            "max_steps",
+            "start_in_bounds",
+            "estimated_cost",
+            "warnings",
+            "errors",
+        ])
+    }
+}
+
+/*  Checks a `Plan` against the limits of the robot and the grid, without simulating it.
+    The commands are only followed as far as `Position::travel` goes, which takes a single
+    step per command, so this stays cheap no matter how many steps there are.
+
+    Patterns are checked for what doesn't make sense (e.g. a negative `radius`) and then
+    expanded to follow the robot, as long as that's worth it (see below). The commands they expand into aren't checked against
+    `MAX_STEPS`, which is a limit for what a client may send: a pattern may well take the
+    robot across the whole grid in a single lane.
+*/
+pub fn validate(plan: &Plan) -> Report {
+    let mut report = Report {
+        commands: plan.commands.len(),
+        start_in_bounds: !plan.start.out_of_bounds(),
+        ..Report::default()
+    };
+
+    if report.commands > MAX_COMMANDS {
+        report.errors.push(Issue::new(
+            "$.commands",
+            format!(
+                "{} commands exceed the limit of {}",
+                report.commands, MAX_COMMANDS
+            ),
+        ));
+    }
+
+    for (axis, value) in [("x", plan.start.x), ("y", plan.start.y)] {
+        if value.unsigned_abs() > FIELD_LIMIT as u32 {
+            report.errors.push(Issue::new(
+                format!("$.start.{}", axis),
+                format!(
+                    "{} is outside of the grid (-{} to {})",
+                    value, FIELD_LIMIT, FIELD_LIMIT
+                ),
+            ));
+        }
+    }
+
+    /*  Once the request is bound to be refused for its size (too many commands or too many
+        steps), the robot isn't followed anymore: expanding thousands of patterns would make
+        validating it about as costly as simulating it. From then on patterns only add their
+        `Pattern::cost` (leaving out the way to them) and steps dropped at the edge of the
+        grid go unnoticed.
+    */
+    let mut position = Some(plan.start).filter(|_| report.commands <= MAX_COMMANDS);
+    for (index, instruction) in plan.commands.iter().enumerate() {
+        let path = format!("$.commands[{}]", index);
+        if report.steps_requested > MAX_COST {
+            position = None;
+        }
+
+        match instruction {
+            Instruction::Command(command) => {
+                position = check_command(&mut report, &path, position, command)
+            }
+            Instruction::Pattern(pattern) => {
+                if !check_pattern(&mut report, &path, pattern) {
+                    continue;
+                }
+
+                match position {
+                    Some(start) => {
+                        let (commands, destination) = pattern.expand(start);
+                        report.steps_requested +=
+                            commands.iter().map(|c| c.steps as i64).sum::<i64>();
+                        position = Some(destination);
+                    }
+                    None => report.steps_requested += pattern.cost(),
+                }
+            }
+        }
+    }
+
+    report.estimated_cost = report.steps_requested;
+    if report.estimated_cost > MAX_COST {
+        report.errors.push(Issue::new(
+            "$.commands",
+            format!(
+                "{} steps exceed the limit of {} for a single simulation",
+                report.estimated_cost, MAX_COST
+            ),
+        ));
+    } else if report.estimated_cost > COSTLY_STEPS {
+        report.warnings.push(Issue::new(
+            "$.commands",
+            format!(
+                "{} steps take a while to simulate (more than {})",
+                report.estimated_cost, COSTLY_STEPS
+            ),
+        ));
+    }
+
+    report
+}
+
+/*  Checks a plain `Command`, returning the `Position` the robot ends up at afterwards (if
+    it's still being followed).
+*/
+fn check_command(
+    report: &mut Report,
+    path: &str,
+    position: Option<Position>,
+    command: &Command,
+) -> Option<Position> {
+    let path = format!("{}.steps", path);
+    let steps = command.steps;
+
+    report.min_steps = Some(report.min_steps.map_or(steps, |min| min.min(steps)));
+    report.max_steps = Some(report.max_steps.map_or(steps, |max| max.max(steps)));
+
+    if steps < 0 {
+        report.errors.push(Issue::new(
+            path,
+            format!("{} steps can't be negative", steps),
+        ));
+        return position;
+    }
+    if steps > MAX_STEPS {
+        report.errors.push(Issue::new(
+            path.clone(),
+            format!("{} steps exceed the limit of {}", steps, MAX_STEPS),
+        ));
+    }
+    if steps == 0 {
+        report
+            .warnings
+            .push(Issue::new(path.clone(), "Command doesn't move the robot"));
+    }
+
+    report.steps_requested += steps as i64;
+
+    let position = position?;
+    let destination = position.travel(&command.direction, steps);
+    if report.start_in_bounds {
+        let moved = (destination.x as i64 - position.x as i64).abs()
+            + (destination.y as i64 - position.y as i64).abs();
+        if moved < steps as i64 {
+            report.warnings.push(Issue::new(
+                path,
+                format!(
+                    "{} of {} steps would be dropped at the edge of the grid",
+                    steps as i64 - moved,
+                    steps
+                ),
+            ));
+        }
+    }
+
+    Some(destination)
+}
+
+/*  Checks the fields of a `Pattern`, returning whether it can be expanded. Parts of a
+    pattern outside of the grid are only a warning, since the pattern keeps to the grid on
+    its own (see `patterns.rs`).
+*/
+fn check_pattern(report: &mut Report, path: &str, pattern: &Pattern) -> bool {
+    let outside = |path: String, region: &str| {
+        Issue::new(
+            path,
+            format!("{} is outside of the grid and is cut down to it", region),
+        )
+    };
+
+    match pattern {
+        Pattern::Spot { center, radius } => {
+            if *radius < 0 {
+                report.errors.push(Issue::new(
+                    format!("{}.spot.radius", path),
+                    format!("A radius of {} can't be negative", radius),
+                ));
+                return false;
+            }
+            if *radius > MAX_SPOT_RADIUS {
+                report.errors.push(Issue::new(
+                    format!("{}.spot.radius", path),
+                    format!(
+                        "A radius of {} exceeds the limit of {}",
+                        radius, MAX_SPOT_RADIUS
+                    ),
+                ));
+                return false;
+            }
+            if center.out_of_bounds() {
+                report
+                    .warnings
+                    .push(outside(format!("{}.spot.center", path), "Center"));
+            }
+        }
+        Pattern::Edge { region } => {
+            if region.min.out_of_bounds() || region.max.out_of_bounds() {
+                report
+                    .warnings
+                    .push(outside(format!("{}.edge.region", path), "Region"));
+            }
+        }
+        Pattern::RandomBounce { steps, .. } => {
+            if *steps < 0 {
+                report.errors.push(Issue::new(
+                    format!("{}.random_bounce.steps", path),
+                    format!("{} steps can't be negative", steps),
+                ));
+                return false;
+            }
+            if *steps > MAX_BOUNCE_STEPS {
+                report.errors.push(Issue::new(
+                    format!("{}.random_bounce.steps", path),
+                    format!("{} steps exceed the limit of {}", steps, MAX_BOUNCE_STEPS),
+                ));
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/*  Anything that can be checked before it's let into the app. `violations` are all the
+    `Issue`s which make it invalid, it's valid if there are none.
+*/
+pub trait Validate {
+    fn violations(&self) -> Vec<Issue>;
+}
+
+impl Validate for Plan {
+    fn violations(&self) -> Vec<Issue> {
+        validate(self).errors
+    }
+}
+
+/*  `Valid` is a `Payload` which has passed validation. A request body with any violations
+    is rejected with a `422`, listing every single one of them (not just the first one), so
+    a client can fix all of them in one go (see `Error`).
+
+    The body is validated as the `Plan` it was sent as and only converted into what the
+    handler takes (e.g. a `Request`, expanding its patterns) afterwards.
+*/
+pub struct Valid<T>(pub T);
+
+#[async_trait]
+impl<S, B, T> FromRequest<S, B> for Valid<T>
+where
+    T: From<Plan>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(request: HttpRequest<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Payload(plan) = Payload::<Plan>::from_request(request, state).await?;
+
+        let violations = plan.violations();
+        if !violations.is_empty() {
+            return Err(Error::Validation(violations));
+        }
+
+        Ok(Valid(T::from(plan)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::Request,
+        types::{Direction, Region},
+    };
+    use axum::{body::Body, http::header};
+
+    fn command(direction: Direction, steps: i32) -> Command {
+        Command { direction, steps }
+    }
+
+    #[test]
+    fn reports_limits_and_cost() {
+        let request = Request {
+            start: Position {
+                x: FIELD_LIMIT - 1,
+                y: 0,
+            },
+            commands: vec![
+                command(Direction::East, 3),
+                command(Direction::North, 0),
+                command(Direction::West, 10),
+            ],
+        };
+
+        let report = validate(&Plan::from(request));
+
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            (
+                report.commands,
+                report.steps_requested,
+                report.estimated_cost
+            ),
+            (3, 13, 13)
+        );
+        assert_eq!((report.min_steps, report.max_steps), (Some(0), Some(10)));
+        assert!(report.start_in_bounds);
+        assert_eq!(
+            report.warnings,
+            vec![
+                Issue::new(
+                    "$.commands[0].steps",
+                    "2 of 3 steps would be dropped at the edge of the grid"
+                ),
+                Issue::new("$.commands[1].steps", "Command doesn't move the robot"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let request = Request {
+            start: Position {
+                x: FIELD_LIMIT + 1,
+                y: -FIELD_LIMIT - 5,
+            },
+            commands: vec![
+                command(Direction::East, -3),
+                command(Direction::North, MAX_STEPS + 1),
+            ],
+        };
+
+        let report = validate(&Plan::from(request));
+
+        assert!(!report.errors.is_empty());
+        assert!(!report.start_in_bounds);
+        let paths: Vec<&str> = report.errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "$.start.x",
+                "$.start.y",
+                "$.commands[0].steps",
+                "$.commands[1].steps"
+            ]
+        );
+
+        let request = Request {
+            start: Position { x: 0, y: 0 },
+            commands: vec![command(Direction::East, 1); MAX_COMMANDS + 1],
+        };
+        assert_eq!(validate(&Plan::from(request)).errors[0].path, "$.commands");
+    }
+
+    #[test]
+    fn reports_start_at_smallest_integer() {
+        let request = Request {
+            start: Position {
+                x: i32::MIN,
+                y: i32::MIN,
+            },
+            commands: vec![command(Direction::East, MAX_STEPS)],
+        };
+
+        let report = validate(&Plan::from(request));
+
+        assert!(!report.start_in_bounds);
+        let paths: Vec<&str> = report.errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["$.start.x", "$.start.y"]);
+    }
+
+    #[test]
+    fn points_into_patterns_as_sent() {
+        let plan: Plan = serde_json::from_str(
+            r#"{
+                "start": {"x": 0, "y": 0},
+                "commands": [
+                    {"direction": "east", "steps": 1},
+                    {"spot": {"center": {"x": 5, "y": 5}, "radius": -1}},
+                    {"random_bounce": {"seed": 1, "steps": -5}},
+                    {"edge": {"region": {"min": {"x": 0, "y": 0}, "max": {"x": 3, "y": 200000}}}},
+                    {"direction": "north", "steps": -1}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let report = validate(&plan);
+
+        let paths: Vec<&str> = report.errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "$.commands[1].spot.radius",
+                "$.commands[2].random_bounce.steps",
+                "$.commands[4].steps"
+            ]
+        );
+        assert_eq!(report.warnings[0].path, "$.commands[3].edge.region");
+        assert_eq!(report.commands, 5);
+        // The command, then the edge of the region cut down to the grid (and back)
+        assert_eq!(report.steps_requested, 1 + 1 + 2 * (3 + FIELD_LIMIT as i64));
+    }
+
+    #[test]
+    fn limits_patterns() {
+        let plan = |pattern| Plan {
+            start: Position { x: 0, y: 0 },
+            commands: vec![Instruction::Pattern(pattern)],
+        };
+        let bounce = |steps| plan(Pattern::RandomBounce { seed: 1, steps });
+        let spot = |radius| {
+            plan(Pattern::Spot {
+                center: Position { x: 0, y: 0 },
+                radius,
+            })
+        };
+
+        assert!(validate(&bounce(MAX_BOUNCE_STEPS)).errors.is_empty());
+        assert_eq!(
+            validate(&bounce(MAX_BOUNCE_STEPS + 1)).errors,
+            vec![Issue::new(
+                "$.commands[0].random_bounce.steps",
+                "1000001 steps exceed the limit of 1000000"
+            )]
+        );
+
+        assert!(validate(&spot(MAX_SPOT_RADIUS)).errors.is_empty());
+        assert_eq!(
+            validate(&spot(100000)).errors,
+            vec![Issue::new(
+                "$.commands[0].spot.radius",
+                "A radius of 100000 exceeds the limit of 1000"
+            )]
+        );
+    }
+
+    #[test]
+    fn limits_cost() {
+        let spots = Plan {
+            start: Position { x: 0, y: 0 },
+            commands: vec![
+                Instruction::Pattern(Pattern::Spot {
+                    center: Position { x: 0, y: 0 },
+                    radius: MAX_SPOT_RADIUS,
+                });
+                13
+            ],
+        };
+
+        let report = validate(&spots);
+
+        // Every spiral takes (2 * 1000 + 1)^2 - 1 steps and ends in a corner of its square
+        assert_eq!(report.estimated_cost, 13 * 4004000 + 12 * 2 * 1000);
+        assert_eq!(
+            report.errors,
+            vec![Issue::new(
+                "$.commands",
+                "52076000 steps exceed the limit of 50000000 for a single simulation"
+            )]
+        );
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn estimates_patterns_beyond_limits() {
+        let spot = Instruction::Pattern(Pattern::Spot {
+            center: Position { x: 0, y: 0 },
+            radius: MAX_SPOT_RADIUS,
+        });
+        let plan = Plan {
+            start: Position { x: 0, y: 0 },
+            commands: vec![spot; MAX_COMMANDS + 1],
+        };
+
+        // Expanding all of them would take about 40 million commands
+        let report = validate(&plan);
+
+        assert_eq!(report.estimated_cost, (MAX_COMMANDS as i64 + 1) * 4004000);
+        let paths: Vec<&str> = report.errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["$.commands", "$.commands"]);
+    }
+
+    #[tokio::test]
+    async fn accepts_patterns_crossing_the_grid() {
+        let body = r#"{
+            "start": {"x": -100000, "y": 0},
+            "commands": [{"spot": {"center": {"x": 100000, "y": 0}, "radius": 0}}]
+        }"#;
+        let request = HttpRequest::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        let Valid(request) = Valid::<Request>::from_request(request, &())
+            .await
+            .ok()
+            .unwrap();
+
+        // Twice `MAX_STEPS`, which a client couldn't send as a single command
+        assert_eq!(
+            request.commands,
+            vec![Command {
+                direction: Direction::East,
+                steps: 2 * FIELD_LIMIT
+            }]
+        );
+
+        let edge = Plan {
+            start: Position { x: 0, y: 0 },
+            commands: vec![Instruction::Pattern(Pattern::Edge {
+                region: Region {
+                    min: Position { x: 0, y: 0 },
+                    max: Position { x: 1, y: 1 },
+                },
+            })],
+        };
+        assert!(validate(&edge).errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_requests() {
+        let body = r#"{
+            "start": {"x": 100001, "y": 0},
+            "commands": [
+                {"direction": "east", "steps": 2},
+                {"direction": "north", "steps": -1}
+            ]
+        }"#;
+        let request = HttpRequest::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        let error = Valid::<Request>::from_request(request, &())
+            .await
+            .err()
+            .unwrap();
+
+        match error {
+            Error::Validation(violations) => assert_eq!(
+                violations,
+                vec![
+                    Issue::new(
+                        "$.start.x",
+                        "100001 is outside of the grid (-100000 to 100000)"
+                    ),
+                    Issue::new("$.commands[1].steps", "-1 steps can't be negative"),
+                ]
+            ),
+            error => panic!("Unexpected error {:?}", error),
+        }
+    }
+}